@@ -3,26 +3,79 @@ use log::{debug, error, info};
 use std::error::Error;
 use std::str::FromStr;
 
-use raydium_amm::state::AmmInfo;
-use raydium_library::amm::{self, AmmCommands};
 use solana_sdk::instruction::Instruction;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signer::Signer;
+use spl_associated_token_account::get_associated_token_address;
 
 use crate::cache::{Market, Pool};
-use crate::client_helper::ClientHelper;
-use crate::fetch_pool;
+use crate::client_helper::{ClientHelper, ClientHelperTxResult, WalletKeys};
+use crate::fetch_pool::{self, ExtendedAmmInfo};
+use crate::math::{add_slippage_bps, slippage_to_bps, sub_slippage_bps};
+use crate::pool_keys::PoolKeys;
+use crate::quote;
+use crate::token::{self, UserTokenAccount};
 use crate::*;
 
-// Function to build the transaction instructions for adding liquidity
+// base_side of the deposit instruction, 0 fixes the coin amount and 1 the pc amount
+pub const BASE_SIDE_COIN: u64 = 0;
+pub const BASE_SIDE_PC: u64 = 1;
+
+pub use crate::quote::DepositSide;
+
+fn base_side(side: DepositSide) -> u64 {
+    match side {
+        DepositSide::Coin => BASE_SIDE_COIN,
+        DepositSide::Pc => BASE_SIDE_PC,
+    }
+}
+
+// What the deposit is expected to take and mint, and the bounds enforced on-chain, amounts with decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AddLiquidityQuote {
+    pub fixed_side: DepositSide,
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    pub max_coin_amount: u64,
+    pub max_pc_amount: u64,
+    // lower bound of the paired side
+    pub other_amount_min: u64,
+    pub lp_amount: u64,
+}
+
+// Function to build the transaction instructions for adding liquidity, `amount` is the pc side w/o decimals
 pub fn build_add_liquidity_ix(
-    client: &ClientHelper,
-    pool_id: &Pubkey,
-    pool_info: &AmmInfo,
+    client: &impl WalletKeys,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
     amount: u64,
     slippage: f64,
 ) -> Result<Vec<Instruction>, LiquidityError> {
-    // Basic sanity checks, AmmCommands::Deposit do more checks for us already
+    let decimals = pool_info.amm_info.sys_decimal_value;
+    let amount_with_decimals = amount
+        .checked_mul(decimals)
+        .ok_or_else(|| LiquidityError::MultiplicationOverflow)?;
+    let (instructions, _) = build_add_liquidity_exact_ix(
+        client,
+        pool_keys,
+        pool_info,
+        DepositSide::Pc,
+        amount_with_decimals,
+        slippage,
+    )?;
+    Ok(instructions)
+}
+
+// Deposit exactly `amount` (raw, with decimals) of `fixed_side`
+pub fn build_add_liquidity_exact_ix(
+    client: &impl WalletKeys,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
+    fixed_side: DepositSide,
+    amount: u64,
+    slippage: f64,
+) -> Result<(Vec<Instruction>, AddLiquidityQuote), LiquidityError> {
+    // Basic sanity checks, the program does more checks for us already
     if amount == 0 {
         return Err(LiquidityError::AmountZero);
     }
@@ -31,44 +84,122 @@ pub fn build_add_liquidity_ix(
         return Err(LiquidityError::SlippageOutOfRange);
     }
 
-    // Build Tx
-    let decimals = pool_info.sys_decimal_value;
-    let amount_with_decimals = amount
-        .checked_mul(decimals)
-        .ok_or_else(|| LiquidityError::MultiplicationOverflow)?;
+    let quote = bound_deposit(pool_info, fixed_side, amount, slippage)?;
+    debug!("Expected deposit: {:?}", quote);
+
+    // wrapped SOL sides are funded with their max amount, whatever the pool does not take is unwrapped afterwards
+    let owner = client.owner();
+    let payer = client.payer();
+    let coin =
+        UserTokenAccount::source(&payer, &owner, &pool_keys.coin_mint, quote.max_coin_amount);
+    let pc = UserTokenAccount::source(&payer, &owner, &pool_keys.pc_mint, quote.max_pc_amount);
+    let lp = UserTokenAccount::destination(&payer, &owner, &pool_keys.lp_mint);
+    let deposit_ix = raydium_amm::instruction::deposit(
+        &pool_keys.amm_program,
+        &pool_keys.amm_id,
+        &pool_keys.amm_authority,
+        &pool_keys.amm_open_orders,
+        &pool_keys.amm_target_orders,
+        &pool_keys.lp_mint,
+        &pool_keys.coin_vault,
+        &pool_keys.pc_vault,
+        &pool_keys.market_id,
+        &pool_keys.market_event_queue,
+        &coin.address,
+        &pc.address,
+        &lp.address,
+        &owner,
+        quote.max_coin_amount,
+        quote.max_pc_amount,
+        base_side(fixed_side),
+        Some(quote.other_amount_min),
+    )
+    .map_err(|_| LiquidityError::InstructionGenerationFailed)?;
+    let mut instructions = [coin.setup, pc.setup, lp.setup].concat();
+    instructions.push(deposit_ix);
+    instructions.extend([coin.cleanup, pc.cleanup].concat());
+    Ok((instructions, quote))
+}
+
+fn bound_deposit(
+    pool_info: &ExtendedAmmInfo,
+    fixed_side: DepositSide,
+    amount: u64,
+    slippage: f64,
+) -> Result<AddLiquidityQuote, LiquidityError> {
+    let deposit = quote::quote_deposit(pool_info, fixed_side, amount)
+        .ok_or(LiquidityError::MultiplicationOverflow)?;
+    let other_amount = match fixed_side {
+        DepositSide::Coin => deposit.pc_amount,
+        DepositSide::Pc => deposit.coin_amount,
+    };
 
-    let cmd = AmmCommands::Deposit {
-        pool_id: *pool_id,
-        deposit_token_coin: Option::None,
-        deposit_token_pc: Option::None,
-        recipient_token_lp: Option::None,
-        amount_specified: amount_with_decimals,
-        another_min_limit: false,
-        base_coin: false,
+    // Bound the paired side both ways, the program rejects the deposit if the ratio moved further
+    let slippage_bps = slippage_to_bps(slippage);
+    let max_other_amount = add_slippage_bps(other_amount, slippage_bps)
+        .ok_or(LiquidityError::MultiplicationOverflow)?;
+    let other_amount_min = sub_slippage_bps(other_amount, slippage_bps)
+        .ok_or(LiquidityError::MultiplicationOverflow)?;
+
+    let (max_coin_amount, max_pc_amount) = match fixed_side {
+        DepositSide::Coin => (amount, max_other_amount),
+        DepositSide::Pc => (max_other_amount, amount),
     };
-    let result =
-        amm::process_amm_commands(cmd, &client.config).map_err(LiquidityError::from)?;
-    result.ok_or(LiquidityError::NoInstructions)
+    Ok(AddLiquidityQuote {
+        fixed_side,
+        coin_amount: deposit.coin_amount,
+        pc_amount: deposit.pc_amount,
+        max_coin_amount,
+        max_pc_amount,
+        other_amount_min,
+        lp_amount: deposit.lp_amount,
+    })
 }
 
 // Function to add liquidity
 pub fn add_liquidity(
     client: &ClientHelper,
-    pool_id: &Pubkey,
-    pool_info: &AmmInfo,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
     amount: u64,
     slippage: f64,
     dryrun: bool,
-) -> Result<(), Box<dyn Error>> {
-    let instructions = build_add_liquidity_ix(client, pool_id, pool_info, amount, slippage)?;
-    client.process_transaction(&instructions, dryrun);
+) -> Result<ClientHelperTxResult, Box<dyn Error>> {
+    let instructions = build_add_liquidity_ix(client, pool_keys, pool_info, amount, slippage)?;
+    let result = client.process_transaction(&instructions, dryrun)?;
     info!("{}", Colour::Green.paint("Liquidity successfully added"));
-    Ok(())
+    Ok(result)
+}
+
+// Function to add liquidity with an exact amount of one side
+pub fn add_liquidity_exact(
+    client: &ClientHelper,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
+    fixed_side: DepositSide,
+    amount: u64,
+    slippage: f64,
+    dryrun: bool,
+) -> Result<ClientHelperTxResult, Box<dyn Error>> {
+    let (instructions, quote) =
+        build_add_liquidity_exact_ix(client, pool_keys, pool_info, fixed_side, amount, slippage)?;
+    info!(
+        "Depositing {} coin and {} pc for {} LP",
+        quote.coin_amount, quote.pc_amount, quote.lp_amount
+    );
+    let result = client.process_transaction(&instructions, dryrun)?;
+    info!("{}", Colour::Green.paint("Liquidity successfully added"));
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::backend::MockBackend;
+    use crate::client_helper::tests::mock_client;
+    use crate::program_error::PoolProgramError;
+    use crate::program_test::Harness;
+    use std::rc::Rc;
 
     #[test]
     fn test_add_liquidity() {
@@ -77,6 +208,7 @@ mod tests {
         let market = Market::read_from_cache().unwrap();
         let pool = Pool::read_from_cache(&market.market_id).unwrap();
         let pool_pubkey = Pubkey::from_str(&pool.amm_id).unwrap();
+        let pool_keys = PoolKeys::from_cache(&pool, &market).unwrap();
 
         // BEFORE
         let pool_info = client
@@ -92,19 +224,12 @@ mod tests {
         // ******************
         // INPUTS
         // ******************
-        // for the version where you can deposit exact amount of mintA and mintB please refer to tests/add_liquidity.ts
+        // to deposit an exact amount of coin or pc use `add_liquidity_exact`
         let amount = 1; // w/o decimals
         let slippage = 0.01;
 
         // Add liquidity
-        let result = add_liquidity(
-            &client,
-            &pool_pubkey,
-            &pool_info.amm_info,
-            amount,
-            slippage,
-            false,
-        );
+        let result = add_liquidity(&client, &pool_keys, &pool_info, amount, slippage, false);
         assert!(
             result.is_ok(),
             "add_liquidity failed with error: {:?}",
@@ -112,7 +237,6 @@ mod tests {
         );
 
         // AFTER
-        client.tests_wait_for_confirmation();
         let after_pool_info = client
             .fetch_extended_amm_info(&pool_pubkey)
             .expect("Failed to fetch final pool balances");
@@ -170,4 +294,217 @@ mod tests {
             Colour::Green.paint(after_pool_info.pc_vault_balance.to_string())
         );
     }
+
+    #[test]
+    fn test_offline_build_add_liquidity_ix() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let pre_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+
+        // EXECUTE
+        let amount = 1; // w/o decimals
+        let instructions =
+            build_add_liquidity_ix(&client, &pool_keys, &pool_info, amount, 0.01).unwrap();
+        let result = client.process_transaction(&instructions, false);
+        assert!(
+            result.is_ok(),
+            "add liquidity transaction failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_pool_info = harness.fetch_extended_amm_info().unwrap();
+        let after_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+        assert_eq!(
+            after_pool_info.pc_vault_balance - pool_info.pc_vault_balance,
+            amount * pool_info.amm_info.sys_decimal_value,
+            "PC vault should have received exactly the requested amount"
+        );
+        assert!(
+            after_user_lp_amount > pre_user_lp_amount,
+            "User LP amount should have increased"
+        );
+        assert_eq!(
+            after_pool_info.lp_amount - pool_info.lp_amount,
+            after_user_lp_amount - pre_user_lp_amount,
+            "Pool LP amount should have increased by the minted amount"
+        );
+    }
+
+    #[test]
+    fn test_offline_add_liquidity_with_sponsor() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.sponsored_client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let pre_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+        let pre_user_lamports = harness.fetch_lamports(&harness.user.pubkey()).unwrap();
+        let pre_sponsor_lamports = harness.fetch_lamports(&harness.sponsor.pubkey()).unwrap();
+
+        // EXECUTE
+        let instructions =
+            build_add_liquidity_ix(&client, &pool_keys, &pool_info, 1, 0.01).unwrap();
+        let result = client.process_transaction(&instructions, false);
+        assert!(
+            result.is_ok(),
+            "sponsored add liquidity failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+        assert!(
+            after_user_lp_amount > pre_user_lp_amount,
+            "LP should be minted to the owner"
+        );
+        assert_eq!(
+            harness.fetch_lamports(&harness.user.pubkey()).unwrap(),
+            pre_user_lamports,
+            "The owner should not pay anything"
+        );
+        assert!(
+            harness.fetch_lamports(&harness.sponsor.pubkey()).unwrap() < pre_sponsor_lamports,
+            "The sponsor should pay the fees"
+        );
+    }
+
+    #[test]
+    fn test_offline_add_liquidity_enforces_slippage() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // quote from a stale view where the coin side looks half as deep as it is
+        let mut stale_pool_info = harness.fetch_extended_amm_info().unwrap();
+        stale_pool_info.coin_vault_balance /= 2;
+
+        // EXECUTE
+        let instructions =
+            build_add_liquidity_ix(&client, &pool_keys, &stale_pool_info, 1, 0.01).unwrap();
+        let result = client.process_transaction(&instructions, false);
+
+        // EXPECT
+        let error = result.expect_err("deposit outside the slippage bounds should fail");
+        assert_eq!(
+            error.program_error(),
+            Some(&PoolProgramError::ExceededSlippage),
+            "unexpected error: {:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_offline_add_liquidity_exact_coin() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let pre_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+
+        // EXECUTE
+        let amount = 3 * pool_info.amm_info.sys_decimal_value; // with decimals
+        let (instructions, quote) = build_add_liquidity_exact_ix(
+            &client,
+            &pool_keys,
+            &pool_info,
+            DepositSide::Coin,
+            amount,
+            0.01,
+        )
+        .unwrap();
+        let result = client.process_transaction(&instructions, false);
+        assert!(
+            result.is_ok(),
+            "exact coin deposit failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_pool_info = harness.fetch_extended_amm_info().unwrap();
+        let after_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+        assert_eq!(quote.coin_amount, amount);
+        assert_eq!(
+            after_pool_info.coin_vault_balance - pool_info.coin_vault_balance,
+            amount,
+            "Coin vault should have received exactly the requested amount"
+        );
+        assert_eq!(
+            after_pool_info.pc_vault_balance - pool_info.pc_vault_balance,
+            quote.pc_amount,
+            "PC vault should have received the quoted paired amount"
+        );
+        assert_eq!(
+            after_user_lp_amount - pre_user_lp_amount,
+            quote.lp_amount,
+            "User should have received the quoted LP amount"
+        );
+    }
+
+    #[test]
+    fn test_build_add_liquidity_ix_wraps_native_side() {
+        // SETUP
+        let client = mock_client(Rc::new(MockBackend::default()));
+        let market = Market::read_from_cache().unwrap();
+        let pool = Pool::read_from_cache(&market.market_id).unwrap();
+        let mut pool_keys = PoolKeys::from_cache(&pool, &market).unwrap();
+        pool_keys.pc_mint = spl_token::native_mint::id();
+        let mut amm_info = raydium_amm::state::AmmInfo::default();
+        amm_info.lp_amount = 1_000_000_000;
+        let pool_info = ExtendedAmmInfo::new(amm_info, 1_000_000_000, 1_000_000_000, 1_000_000_000);
+
+        // EXECUTE
+        let (instructions, quote) = build_add_liquidity_exact_ix(
+            &client,
+            &pool_keys,
+            &pool_info,
+            DepositSide::Pc,
+            1_000_000,
+            0.01,
+        )
+        .unwrap();
+
+        // EXPECT
+        let owner = client.user_keypair.pubkey();
+        let sync_native = spl_token::instruction::sync_native(&spl_token::id(), &Pubkey::default())
+            .unwrap()
+            .data;
+        let close_account = spl_token::instruction::close_account(
+            &spl_token::id(),
+            &Pubkey::default(),
+            &owner,
+            &owner,
+            &[],
+        )
+        .unwrap()
+        .data;
+        let deposit_index = instructions
+            .iter()
+            .position(|ix| ix.program_id == pool_keys.amm_program)
+            .unwrap();
+        assert!(
+            instructions[..deposit_index]
+                .iter()
+                .any(|ix| ix.program_id == spl_token::id() && ix.data == sync_native),
+            "SOL should be wrapped before the deposit"
+        );
+        assert!(
+            instructions[deposit_index + 1..]
+                .iter()
+                .any(|ix| ix.program_id == spl_token::id() && ix.data == close_account),
+            "wSOL account should be closed after the deposit"
+        );
+        assert_eq!(quote.max_pc_amount, 1_000_000);
+    }
 }
@@ -6,40 +6,32 @@ use log::{debug, error, info};
 use std::error::Error;
 use std::str::FromStr;
 
-use raydium_amm::state::AmmInfo;
-use raydium_library::amm::{self, AmmCommands};
 use solana_sdk::instruction::Instruction;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signer::Signer;
 
 use crate::add_liquidity::build_add_liquidity_ix;
 use crate::cache::{Market, Pool};
-use crate::client_helper::ClientHelper;
-use crate::fetch_pool;
+use crate::client_helper::{ClientHelper, ClientHelperTxResult};
+use crate::fetch_pool::{self, ExtendedAmmInfo};
+use crate::pool_keys::PoolKeys;
 use crate::remove_liquidity::build_remove_liquidity_ix;
 use crate::*;
 
 pub fn add_remove_liquidity(
     client: &ClientHelper,
-    pool_id: &Pubkey,
-    pool_info: &AmmInfo,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
     add_amount_no_dec: u64,
     remove_amount_no_dec: u64,
     slippage: f64,
     dryrun: bool,
-) -> Result<(), Box<dyn Error>> {
-    let add_ix =
-        build_add_liquidity_ix(client, pool_id, pool_info, add_amount_no_dec, slippage)?;
-    let remove_ix = build_remove_liquidity_ix(
-        client,
-        pool_id,
-        pool_info,
-        remove_amount_no_dec,
-        slippage,
-    )?;
+) -> Result<ClientHelperTxResult, Box<dyn Error>> {
+    let add_ix = build_add_liquidity_ix(client, pool_keys, pool_info, add_amount_no_dec, slippage)?;
+    let (remove_ix, _) =
+        build_remove_liquidity_ix(client, pool_keys, pool_info, remove_amount_no_dec, slippage)?;
     let ixs: Vec<Instruction> = add_ix.into_iter().chain(remove_ix).collect();
-    client.process_transaction(&ixs, dryrun);
-    Ok(())
+    Ok(client.process_transaction(&ixs, dryrun)?)
 }
 
 mod tests {
@@ -57,6 +49,7 @@ mod tests {
         let market = Market::read_from_cache().unwrap();
         let pool = Pool::read_from_cache(&market.market_id).unwrap();
         let pool_pubkey = Pubkey::from_str(&pool.amm_id).unwrap();
+        let pool_keys = PoolKeys::from_cache(&pool, &market).unwrap();
 
         // BEFORE
         let pool_info = client
@@ -78,8 +71,8 @@ mod tests {
         let dryrun = false;
         add_remove_liquidity(
             &client,
-            &pool_pubkey,
-            &pool_info.amm_info,
+            &pool_keys,
+            &pool_info,
             add_amount_no_dec,
             remove_amount_no_dec,
             slippage,
@@ -88,7 +81,6 @@ mod tests {
         .unwrap();
 
         // AFTER
-        client.tests_wait_for_confirmation();
         let after_pool_info = client
             .fetch_extended_amm_info(&pool_pubkey)
             .expect("Failed to fetch final pool balances");
@@ -0,0 +1,444 @@
+// Send + Sync counterpart of ClientHelper on the nonblocking RPC client, so one process can
+// drive many pools from concurrent tokio tasks. Instructions come from the same builders.
+use ansi_term::Colour;
+use anyhow::Result;
+use log::{debug, info};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::signer::SignerError;
+use solana_sdk::transaction::Transaction;
+use spl_token::state::Account as TokenAccount;
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use thiserror::Error;
+
+use crate::add_liquidity::build_add_liquidity_ix;
+use crate::cache::default_cache_dir;
+use crate::client_helper::{ClientHelper, ClientHelperError, ClientHelperTxResult, WalletKeys};
+use crate::cluster::Cluster;
+use crate::compute_budget::ComputeBudgetConfig;
+use crate::confirmation::{
+    check_status, timeout, ConfirmationConfig, ConfirmationError, ConfirmationReport,
+};
+use crate::fetch_pool::{decode_amm_info, decode_open_orders_totals, ExtendedAmmInfo};
+use crate::pool_keys::PoolKeys;
+use crate::remove_liquidity::build_remove_liquidity_ix;
+use crate::transaction_builder::{confirmation_error, sign_message, TransactionBuilder};
+
+pub type AsyncSigner = Arc<dyn Signer + Send + Sync>;
+
+pub struct AsyncClientHelper {
+    pub rpc_client: Arc<RpcClient>,
+    pub user_keypair: Arc<Keypair>,
+    pub payer: AsyncSigner,
+    pub signing_keypairs: Vec<AsyncSigner>,
+    // signatures are polled, the websocket url is not used
+    pub confirmation: ConfirmationConfig,
+    pub compute_budget: ComputeBudgetConfig,
+    pub cluster: Cluster,
+    pub amm_program: Pubkey,
+    pub market_program: Pubkey,
+    pub cache_dir: PathBuf,
+}
+
+#[derive(Debug, Error)]
+pub enum AsyncClientError {
+    // a signer behind an Rc cannot be moved to another thread
+    #[error("Signer {0} cannot be shared with the async client, attach it with with_fee_payer")]
+    UnsharedSigner(Pubkey),
+
+    #[error("Invalid user keypair: {0}")]
+    Keypair(String),
+}
+
+impl AsyncClientHelper {
+    // The user pays for its own transactions, reads use the confirmation commitment.
+    // `url` serves `cluster`, programs and cache directory default like ClientHelperBuilder.
+    pub fn new(
+        cluster: Cluster,
+        url: &str,
+        user_keypair: Keypair,
+        confirmation: ConfirmationConfig,
+    ) -> Self {
+        let rpc_client = Arc::new(RpcClient::new_with_commitment(
+            url.to_string(),
+            confirmation.commitment,
+        ));
+        let user_keypair = Arc::new(user_keypair);
+        let fee_payer = Arc::clone(&user_keypair) as AsyncSigner;
+        Self {
+            rpc_client,
+            user_keypair,
+            payer: Arc::clone(&fee_payer),
+            signing_keypairs: vec![fee_payer],
+            confirmation,
+            compute_budget: ComputeBudgetConfig::default(),
+            amm_program: cluster.amm_program(),
+            market_program: cluster.market_program(),
+            cluster,
+            cache_dir: default_cache_dir(),
+        }
+    }
+
+    // Sponsor paying fees and rent, the user keeps owning the tokens and signs as well
+    pub fn with_fee_payer(mut self, payer: AsyncSigner) -> Self {
+        self.payer = Arc::clone(&payer);
+        self.signing_keypairs = vec![payer];
+        self
+    }
+
+    pub async fn process_transaction(
+        &self,
+        instructions: &[Instruction],
+        dryrun: bool,
+    ) -> Result<ClientHelperTxResult, ClientHelperError> {
+        let blockhash = self
+            .rpc_client
+            .get_latest_blockhash()
+            .await
+            .map_err(anyhow::Error::from)?;
+        let builder = TransactionBuilder::new(
+            instructions,
+            self.payer.pubkey(),
+            &self.compute_budget,
+            self.amm_program,
+        );
+        let recent_fees = match builder.fee_accounts() {
+            Some(accounts) => self
+                .rpc_client
+                .get_recent_prioritization_fees(&accounts)
+                .await
+                .map_err(anyhow::Error::from)?
+                .into_iter()
+                .map(|fee| fee.prioritization_fee)
+                .collect(),
+            None => Vec::new(),
+        };
+        let mut builder = builder.with_recent_fees(&recent_fees);
+        let mut txn = self.sign(builder.message(), blockhash)?;
+
+        // Always simulate the transaction
+        let sim_result = self
+            .rpc_client
+            .simulate_transaction(&txn)
+            .await
+            .map_err(anyhow::Error::from)?
+            .value;
+        if builder.simulated(&sim_result)? {
+            txn = self.sign(builder.message(), blockhash)?;
+        }
+
+        if dryrun {
+            return Ok(ClientHelperTxResult {
+                simulation_result: sim_result,
+                signature: None,
+                confirmation: None,
+            });
+        }
+
+        let sent_at = Instant::now();
+        let signature = self
+            .rpc_client
+            .send_transaction_with_config(
+                &txn,
+                RpcSendTransactionConfig {
+                    skip_preflight: true,
+                    ..RpcSendTransactionConfig::default()
+                },
+            )
+            .await
+            .map_err(|e| ClientHelperError::Send(e.into()))?;
+        debug!(
+            "\nTransaction {} sent in {:?}\n\n",
+            signature,
+            sent_at.elapsed()
+        );
+
+        // Only report the signature once it reached the configured commitment
+        let confirmation = match self.poll_signature_status(&signature, sent_at).await {
+            Ok(report) => report,
+            Err(e) => {
+                let blockhash_valid = match e {
+                    ConfirmationError::Timeout { .. } => self
+                        .rpc_client
+                        .is_blockhash_valid(&blockhash, self.rpc_client.commitment())
+                        .await
+                        .ok(),
+                    _ => None,
+                };
+                return Err(confirmation_error(signature, e, blockhash_valid));
+            }
+        };
+
+        Ok(ClientHelperTxResult {
+            simulation_result: sim_result,
+            signature: Some(signature),
+            confirmation: Some(confirmation),
+        })
+    }
+
+    // Kept out of the async fns, the borrowed signers are not Send
+    fn sign(&self, message: Message, blockhash: Hash) -> Result<Transaction, SignerError> {
+        let candidates = [
+            self.payer.as_ref() as &dyn Signer,
+            self.user_keypair.as_ref() as &dyn Signer,
+        ]
+        .into_iter()
+        .chain(
+            self.signing_keypairs
+                .iter()
+                .map(|kp| kp.as_ref() as &dyn Signer),
+        );
+        sign_message(message, candidates, blockhash)
+    }
+
+    async fn poll_signature_status(
+        &self,
+        signature: &Signature,
+        sent_at: Instant,
+    ) -> Result<ConfirmationReport, ConfirmationError> {
+        let mut report = ConfirmationReport::new(*signature);
+        loop {
+            let statuses = self
+                .rpc_client
+                .get_signature_statuses(&[*signature])
+                .await
+                .map_err(anyhow::Error::from)?;
+            if let Some(Some(status)) = statuses.value.into_iter().next() {
+                if check_status(&mut report, status, &self.confirmation, sent_at)? {
+                    debug!("\nConfirmation: {:?}\n\n", report);
+                    return Ok(report);
+                }
+            }
+            if sent_at.elapsed() >= self.confirmation.timeout {
+                return Err(timeout(signature, &self.confirmation, sent_at));
+            }
+            tokio::time::sleep(self.confirmation.poll_interval).await;
+        }
+    }
+
+    pub async fn get_account(&self, address: &Pubkey) -> Result<Account> {
+        self.rpc_client
+            .get_account_with_commitment(address, self.rpc_client.commitment())
+            .await?
+            .value
+            .ok_or_else(|| anyhow::anyhow!("Account not found: {}", address))
+    }
+
+    pub async fn fetch_extended_amm_info(&self, pool_id: &Pubkey) -> Result<ExtendedAmmInfo> {
+        let amm_info = decode_amm_info(&self.get_account(pool_id).await?.data)?;
+
+        // vaults and open orders in a single round-trip
+        let addresses = [amm_info.coin_vault, amm_info.pc_vault, amm_info.open_orders];
+        let accounts = self
+            .rpc_client
+            .get_multiple_accounts_with_commitment(&addresses, self.rpc_client.commitment())
+            .await?
+            .value;
+        let mut accounts = addresses.iter().zip(accounts).map(|(address, account)| {
+            account.ok_or_else(|| anyhow::anyhow!("Account not found: {}", address))
+        });
+        let mut next = || accounts.next().expect("one account per address");
+        let coin_vault_balance = TokenAccount::unpack(&next()?.data)?.amount;
+        let pc_vault_balance = TokenAccount::unpack(&next()?.data)?.amount;
+        let (open_orders_coin_total, open_orders_pc_total) =
+            decode_open_orders_totals(&next()?.data)?;
+
+        Ok(ExtendedAmmInfo::new(
+            amm_info,
+            amm_info.lp_amount,
+            coin_vault_balance,
+            pc_vault_balance,
+        )
+        .with_open_orders(open_orders_coin_total, open_orders_pc_total))
+    }
+}
+
+// Same owner, cluster and settings as the blocking client, the RPC client is rebuilt on the
+// same url. A sponsor or extra signer cannot be copied out of its Rc, so converting a client
+// that has one fails; convert the unsponsored client and attach it with `with_fee_payer`.
+impl TryFrom<&ClientHelper> for AsyncClientHelper {
+    type Error = AsyncClientError;
+
+    fn try_from(client: &ClientHelper) -> Result<Self, Self::Error> {
+        let owner = client.user_keypair.pubkey();
+        let signers = std::iter::once(&client.payer).chain(&client.signing_keypairs);
+        if let Some(signer) = signers.map(|s| s.pubkey()).find(|pubkey| *pubkey != owner) {
+            return Err(AsyncClientError::UnsharedSigner(signer));
+        }
+        let user_keypair = Keypair::from_bytes(&client.user_keypair.to_bytes())
+            .map_err(|e| AsyncClientError::Keypair(e.to_string()))?;
+        let async_client = Self::new(
+            client.cluster.clone(),
+            client.config.cluster().url(),
+            user_keypair,
+            client.confirmation.clone(),
+        );
+        Ok(Self {
+            compute_budget: client.compute_budget.clone(),
+            amm_program: client.amm_program,
+            market_program: client.market_program,
+            cache_dir: client.cache_dir.clone(),
+            ..async_client
+        })
+    }
+}
+
+impl WalletKeys for AsyncClientHelper {
+    fn owner(&self) -> Pubkey {
+        self.user_keypair.pubkey()
+    }
+
+    fn payer(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+}
+
+// Async add_liquidity::add_liquidity
+pub async fn add_liquidity(
+    client: &AsyncClientHelper,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
+    amount: u64,
+    slippage: f64,
+    dryrun: bool,
+) -> Result<ClientHelperTxResult, Box<dyn Error + Send + Sync>> {
+    let instructions = build_add_liquidity_ix(client, pool_keys, pool_info, amount, slippage)?;
+    let result = client.process_transaction(&instructions, dryrun).await?;
+    info!("{}", Colour::Green.paint("Liquidity successfully added"));
+    Ok(result)
+}
+
+// Async remove_liquidity::remove_liquidity
+pub async fn remove_liquidity(
+    client: &AsyncClientHelper,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
+    input_lp_amount: u64,
+    slippage_limit: f64,
+    dryrun: bool,
+) -> Result<ClientHelperTxResult, Box<dyn Error + Send + Sync>> {
+    let (instructions, quote) = build_remove_liquidity_ix(
+        client,
+        pool_keys,
+        pool_info,
+        input_lp_amount,
+        slippage_limit,
+    )?;
+    info!(
+        "Removing {} LP for at least {} coin and {} pc",
+        quote.lp_amount, quote.min_coin_amount, quote.min_pc_amount
+    );
+    let result = client.process_transaction(&instructions, dryrun).await?;
+    info!("{}", Colour::Green.paint("Liquidity successfully removed"));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use crate::cache::{Market, Pool};
+    use crate::client_helper::tests::mock_client;
+    use std::rc::Rc;
+    use std::str::FromStr;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn test_async_client_is_send_sync() {
+        assert_send_sync::<AsyncClientHelper>();
+    }
+
+    #[test]
+    fn test_async_client_with_fee_payer() {
+        let confirmation = ConfirmationConfig::default();
+        let client = AsyncClientHelper::new(
+            Cluster::Localnet,
+            "http://localhost:8899",
+            Keypair::new(),
+            confirmation,
+        );
+        assert_eq!(client.cluster, Cluster::Localnet);
+        assert_eq!(client.amm_program, Cluster::Localnet.amm_program());
+        let sponsor = Arc::new(Keypair::new()) as AsyncSigner;
+        let client = client.with_fee_payer(Arc::clone(&sponsor));
+        assert_eq!(client.payer(), sponsor.pubkey());
+        assert_ne!(client.owner(), sponsor.pubkey());
+
+        let transfer =
+            solana_sdk::system_instruction::transfer(&client.owner(), &Pubkey::new_unique(), 1);
+        let message = Message::new(&[transfer], Some(&client.payer()));
+        let txn = client.sign(message, Hash::default()).unwrap();
+        assert_eq!(txn.signatures.len(), 2);
+        assert!(txn.verify().is_ok());
+    }
+
+    #[test]
+    fn test_async_client_try_from_client_helper() {
+        let mut client = mock_client(Rc::new(MockBackend::default()));
+        client.compute_budget.simulate_unit_limit = true;
+        let async_client = AsyncClientHelper::try_from(&client).unwrap();
+        assert_eq!(async_client.owner(), client.owner());
+        assert_eq!(async_client.payer(), client.owner());
+        assert_eq!(async_client.cluster, client.cluster);
+        assert_eq!(async_client.amm_program, client.amm_program);
+        assert_eq!(async_client.market_program, client.market_program);
+        assert_eq!(async_client.cache_dir, client.cache_dir);
+        assert!(async_client.compute_budget.simulate_unit_limit);
+
+        // the sponsor is behind an Rc and cannot be carried
+        let sponsor = Rc::new(Keypair::new()) as Rc<dyn Signer>;
+        client.payer = Rc::clone(&sponsor);
+        client.signing_keypairs = vec![Rc::clone(&sponsor)];
+        assert!(matches!(
+            AsyncClientHelper::try_from(&client),
+            Err(AsyncClientError::UnsharedSigner(pubkey)) if pubkey == sponsor.pubkey()
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_async_add_liquidity_from_concurrent_tasks() {
+        // SETUP
+        let client = Arc::new(AsyncClientHelper::try_from(&ClientHelper::default()).unwrap());
+        let market = Market::read_from_cache().unwrap();
+        let pool = Pool::read_from_cache(&market.market_id).unwrap();
+        let pool_pubkey = Pubkey::from_str(&pool.amm_id).unwrap();
+        let pool_keys = Arc::new(PoolKeys::from_cache(&pool, &market).unwrap());
+
+        // EXECUTE
+        let tasks: Vec<_> = (0..2)
+            .map(|_| {
+                let client = Arc::clone(&client);
+                let pool_keys = Arc::clone(&pool_keys);
+                tokio::spawn(async move {
+                    let pool_info = client.fetch_extended_amm_info(&pool_pubkey).await?;
+                    add_liquidity(&client, &pool_keys, &pool_info, 1, 0.01, true).await
+                })
+            })
+            .collect();
+
+        // EXPECT
+        for task in tasks {
+            let result = task.await.unwrap();
+            assert!(
+                result.is_ok(),
+                "add_liquidity dryrun failed with error: {:?}",
+                result.unwrap_err()
+            );
+            assert!(
+                result.unwrap().signature.is_none(),
+                "dryrun should not send"
+            );
+        }
+    }
+}
@@ -0,0 +1,409 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use raydium_library::common;
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcSendTransactionConfig, RpcTransactionConfig};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::account::Account;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::option_serializer::OptionSerializer;
+use solana_transaction_status::{
+    TransactionConfirmationStatus, TransactionStatus, UiTransactionEncoding,
+};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+/// Everything ClientHelper needs from a cluster, so the same liquidity code runs against
+/// an RPC node, a local program-test bank or an in-memory fixture
+pub trait TransactionBackend {
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>>;
+
+    fn get_latest_blockhash(&self) -> Result<Hash>;
+
+    // Backends that process transactions synchronously never see a blockhash expire
+    fn is_blockhash_valid(&self, _blockhash: &Hash) -> Result<bool> {
+        Ok(true)
+    }
+
+    fn simulate_transaction(&self, txn: &Transaction) -> Result<RpcSimulateTransactionResult>;
+
+    // Submits without waiting, see `confirmation::confirm_transaction`
+    fn send_transaction(&self, txn: &Transaction) -> Result<Signature>;
+
+    fn get_signature_status(&self, signature: &Signature) -> Result<Option<TransactionStatus>>;
+
+    // Compute unit prices paid in recent slots by transactions writing any of the addresses,
+    // local backends have no fee market
+    fn get_recent_prioritization_fees(&self, _addresses: &[Pubkey]) -> Result<Vec<u64>> {
+        Ok(Vec::new())
+    }
+
+    fn get_transaction_logs(&self, signature: &Signature) -> Result<Vec<String>> {
+        Err(anyhow::anyhow!(
+            "Transaction logs are not available on this backend: {}",
+            signature
+        ))
+    }
+}
+
+// ******************
+// RPC
+// ******************
+pub struct RpcBackend {
+    pub client: Rc<RpcClient>,
+}
+
+impl RpcBackend {
+    pub fn new(client: Rc<RpcClient>) -> Self {
+        Self { client }
+    }
+}
+
+impl TransactionBackend for RpcBackend {
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .client
+            .get_account_with_commitment(address, self.client.commitment())?
+            .value)
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.client.get_latest_blockhash()?)
+    }
+
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        Ok(self
+            .client
+            .is_blockhash_valid(blockhash, self.client.commitment())?)
+    }
+
+    fn simulate_transaction(&self, txn: &Transaction) -> Result<RpcSimulateTransactionResult> {
+        let result =
+            common::simulate_transaction(&self.client, txn, false, self.client.commitment())?;
+        Ok(result.value)
+    }
+
+    fn send_transaction(&self, txn: &Transaction) -> Result<Signature> {
+        // preflight is covered by the simulation done in ClientHelper::process_transaction
+        let config = RpcSendTransactionConfig {
+            skip_preflight: true,
+            ..RpcSendTransactionConfig::default()
+        };
+        Ok(self.client.send_transaction_with_config(txn, config)?)
+    }
+
+    fn get_signature_status(&self, signature: &Signature) -> Result<Option<TransactionStatus>> {
+        let statuses = self.client.get_signature_statuses(&[*signature])?.value;
+        Ok(statuses.into_iter().next().flatten())
+    }
+
+    fn get_recent_prioritization_fees(&self, addresses: &[Pubkey]) -> Result<Vec<u64>> {
+        let fees = self.client.get_recent_prioritization_fees(addresses)?;
+        Ok(fees.into_iter().map(|fee| fee.prioritization_fee).collect())
+    }
+
+    fn get_transaction_logs(&self, signature: &Signature) -> Result<Vec<String>> {
+        // getTransaction rejects processed, the closest it serves is confirmed
+        let commitment = match self.client.commitment() {
+            commitment if commitment.is_at_least_confirmed() => commitment,
+            _ => CommitmentConfig::confirmed(),
+        };
+        let config = RpcTransactionConfig {
+            encoding: Some(UiTransactionEncoding::Json),
+            commitment: Some(commitment),
+            max_supported_transaction_version: Some(0),
+        };
+        let tx = self
+            .client
+            .get_transaction_with_config(signature, config)
+            .context("Failed to fetch transaction")?;
+        match tx.transaction.meta.map(|meta| meta.log_messages) {
+            Some(OptionSerializer::Some(logs)) => Ok(logs),
+            _ => Err(anyhow::anyhow!("Transaction has no logs")),
+        }
+    }
+}
+
+// ******************
+// BANKS
+// ******************
+#[cfg(any(test, feature = "banks"))]
+pub use banks::BanksBackend;
+
+#[cfg(any(test, feature = "banks"))]
+mod banks {
+    use super::*;
+    use solana_banks_client::BanksClient;
+    use solana_banks_interface::TransactionConfirmationStatus as BanksConfirmationStatus;
+    use tokio::runtime::Runtime;
+
+    /// Blocking wrapper around a BanksClient, the runtime must be the one the bank was started on
+    pub struct BanksBackend {
+        runtime: Runtime,
+        client: RefCell<BanksClient>,
+    }
+
+    impl BanksBackend {
+        pub fn new(runtime: Runtime, client: BanksClient) -> Self {
+            Self {
+                runtime,
+                client: RefCell::new(client),
+            }
+        }
+
+        pub fn runtime(&self) -> &Runtime {
+            &self.runtime
+        }
+    }
+
+    impl TransactionBackend for BanksBackend {
+        fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+            let mut client = self.client.borrow_mut();
+            Ok(self.runtime.block_on(client.get_account(*address))?)
+        }
+
+        fn get_latest_blockhash(&self) -> Result<Hash> {
+            let mut client = self.client.borrow_mut();
+            Ok(self.runtime.block_on(client.get_latest_blockhash())?)
+        }
+
+        fn simulate_transaction(&self, txn: &Transaction) -> Result<RpcSimulateTransactionResult> {
+            let mut client = self.client.borrow_mut();
+            let simulation = self
+                .runtime
+                .block_on(client.simulate_transaction(txn.clone()))?;
+            let details = simulation.simulation_details;
+            Ok(RpcSimulateTransactionResult {
+                err: simulation.result.and_then(|result| result.err()),
+                logs: details.as_ref().map(|details| details.logs.clone()),
+                accounts: None,
+                units_consumed: details.as_ref().map(|details| details.units_consumed),
+                return_data: None,
+            })
+        }
+
+        fn send_transaction(&self, txn: &Transaction) -> Result<Signature> {
+            let mut client = self.client.borrow_mut();
+            self.runtime
+                .block_on(client.process_transaction(txn.clone()))?;
+            Ok(txn.signatures[0])
+        }
+
+        fn get_signature_status(&self, signature: &Signature) -> Result<Option<TransactionStatus>> {
+            let mut client = self.client.borrow_mut();
+            let status = self
+                .runtime
+                .block_on(client.get_transaction_status(*signature))?;
+            Ok(status.map(|status| TransactionStatus {
+                slot: status.slot,
+                confirmations: status.confirmations,
+                status: status.err.clone().map_or(Ok(()), Err),
+                err: status.err,
+                confirmation_status: status.confirmation_status.map(confirmation_status),
+            }))
+        }
+    }
+
+    // solana-banks-interface has its own copy of the confirmation status enum
+    fn confirmation_status(status: BanksConfirmationStatus) -> TransactionConfirmationStatus {
+        match status {
+            BanksConfirmationStatus::Processed => TransactionConfirmationStatus::Processed,
+            BanksConfirmationStatus::Confirmed => TransactionConfirmationStatus::Confirmed,
+            BanksConfirmationStatus::Finalized => TransactionConfirmationStatus::Finalized,
+        }
+    }
+}
+
+// ******************
+// MOCK
+// ******************
+/// In-memory backend serving recorded accounts, sent transactions are recorded and never executed
+pub struct MockBackend {
+    accounts: RefCell<HashMap<Pubkey, Account>>,
+    logs: RefCell<HashMap<Signature, Vec<String>>>,
+    sent: RefCell<Vec<Transaction>>,
+    simulation: RefCell<RpcSimulateTransactionResult>,
+    prioritization_fees: RefCell<Vec<u64>>,
+    blockhash: Hash,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self {
+            accounts: RefCell::new(HashMap::new()),
+            logs: RefCell::new(HashMap::new()),
+            sent: RefCell::new(Vec::new()),
+            simulation: RefCell::new(RpcSimulateTransactionResult {
+                err: None,
+                logs: Some(Vec::new()),
+                accounts: None,
+                units_consumed: Some(0),
+                return_data: None,
+            }),
+            prioritization_fees: RefCell::new(Vec::new()),
+            blockhash: Hash::new_unique(),
+        }
+    }
+}
+
+impl MockBackend {
+    // Accounts recorded with `solana account <address> --output json`, one file per account
+    pub fn from_fixtures(dir: &Path) -> Result<Self> {
+        let backend = Self::default();
+        for (address, account) in load_account_fixtures(dir)? {
+            backend.set_account(address, account);
+        }
+        Ok(backend)
+    }
+
+    pub fn set_account(&self, address: Pubkey, account: Account) {
+        self.accounts.borrow_mut().insert(address, account);
+    }
+
+    pub fn set_simulation_result(&self, result: RpcSimulateTransactionResult) {
+        *self.simulation.borrow_mut() = result;
+    }
+
+    pub fn set_prioritization_fees(&self, fees: Vec<u64>) {
+        *self.prioritization_fees.borrow_mut() = fees;
+    }
+
+    pub fn set_transaction_logs(&self, signature: Signature, logs: Vec<String>) {
+        self.logs.borrow_mut().insert(signature, logs);
+    }
+
+    pub fn sent_transactions(&self) -> Vec<Transaction> {
+        self.sent.borrow().clone()
+    }
+}
+
+impl TransactionBackend for MockBackend {
+    fn get_account(&self, address: &Pubkey) -> Result<Option<Account>> {
+        Ok(self.accounts.borrow().get(address).cloned())
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Hash> {
+        Ok(self.blockhash)
+    }
+
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        Ok(*blockhash == self.blockhash)
+    }
+
+    fn simulate_transaction(&self, _txn: &Transaction) -> Result<RpcSimulateTransactionResult> {
+        Ok(self.simulation.borrow().clone())
+    }
+
+    fn send_transaction(&self, txn: &Transaction) -> Result<Signature> {
+        self.sent.borrow_mut().push(txn.clone());
+        Ok(txn.signatures[0])
+    }
+
+    // Sent transactions are reported finalized right away
+    fn get_signature_status(&self, signature: &Signature) -> Result<Option<TransactionStatus>> {
+        let sent = self
+            .sent
+            .borrow()
+            .iter()
+            .any(|txn| txn.signatures.first() == Some(signature));
+        Ok(sent.then(|| TransactionStatus {
+            slot: 0,
+            confirmations: None,
+            status: Ok(()),
+            err: None,
+            confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+        }))
+    }
+
+    fn get_recent_prioritization_fees(&self, _addresses: &[Pubkey]) -> Result<Vec<u64>> {
+        Ok(self.prioritization_fees.borrow().clone())
+    }
+
+    fn get_transaction_logs(&self, signature: &Signature) -> Result<Vec<String>> {
+        self.logs
+            .borrow()
+            .get(signature)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No logs recorded for {}", signature))
+    }
+}
+
+#[derive(Deserialize)]
+struct AccountFixture {
+    pubkey: String,
+    account: UiAccountFixture,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UiAccountFixture {
+    lamports: u64,
+    data: (String, String),
+    owner: String,
+    executable: bool,
+    rent_epoch: u64,
+}
+
+pub fn load_account_fixtures(dir: &Path) -> Result<Vec<(Pubkey, Account)>> {
+    let entries =
+        fs::read_dir(dir).with_context(|| format!("Missing fixtures in {}", dir.display()))?;
+    let mut accounts = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let data = fs::read_to_string(&path)?;
+        let fixture: AccountFixture = serde_json::from_str(&data)
+            .with_context(|| format!("Invalid account fixture {}", path.display()))?;
+        let account = Account {
+            lamports: fixture.account.lamports,
+            data: STANDARD.decode(&fixture.account.data.0)?,
+            owner: Pubkey::from_str(&fixture.account.owner)?,
+            executable: fixture.account.executable,
+            rent_epoch: fixture.account.rent_epoch,
+        };
+        accounts.push((Pubkey::from_str(&fixture.pubkey)?, account));
+    }
+    Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+
+    #[test]
+    fn test_mock_backend_records_sent_transactions() {
+        let backend = MockBackend::default();
+        let payer = Keypair::new();
+        let address = Pubkey::new_unique();
+        backend.set_account(address, Account::new(42, 0, &Pubkey::default()));
+        assert_eq!(backend.get_account(&address).unwrap().unwrap().lamports, 42);
+        assert!(backend
+            .get_account(&Pubkey::new_unique())
+            .unwrap()
+            .is_none());
+
+        let txn = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(&payer.pubkey(), &address, 1)],
+            Some(&payer.pubkey()),
+            &[&payer],
+            backend.get_latest_blockhash().unwrap(),
+        );
+        let signature = backend.send_transaction(&txn).unwrap();
+        assert_eq!(signature, txn.signatures[0]);
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+}
@@ -1,16 +1,14 @@
 use log::{debug, error};
+use serde::de::DeserializeOwned;
 use serde::de::Error as SerdeError;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
-#[cfg(feature = "devnet")]
-const PREFIX: &str = "devnet_";
+use crate::cluster::Cluster;
 
-#[cfg(not(feature = "devnet"))]
-const PREFIX: &str = "";
 const CACHE_DIR: &str = "../cache/";
 
 #[derive(Error, Debug)]
@@ -19,13 +17,15 @@ pub enum CacheError {
     FileNotFound(String),
     #[error("Unable to read file: {0}")]
     ReadError(String),
+    #[error("Unable to write file: {0}")]
+    WriteError(String),
     #[error("JSON was not well-formatted")]
     JsonError(#[from] serde_json::Error),
     #[error("Market ID does not match")]
     CacheIdMismatch,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Market {
     pub market_id: String,
@@ -41,29 +41,25 @@ pub struct Market {
 }
 
 impl Market {
+    // The default cluster's market in the shared cache
     pub fn read_from_cache() -> Result<Self, CacheError> {
-        let path = std::env::current_dir()
-            .expect("Unable to get current directory")
-            .join(format!("{}{}market.json", CACHE_DIR, PREFIX));
-        if !Path::new(&path).exists() {
-            error!("File not found at path: {}", path.to_string_lossy());
-            return Err(CacheError::FileNotFound(
-                path.to_string_lossy().into_owned(),
-            ));
-        }
-        let data = fs::read_to_string(&path).map_err(|_| {
-            error!("Unable to read file: {}", path.to_string_lossy());
-            CacheError::ReadError(path.to_string_lossy().into_owned())
-        })?;
-        let v: Value = serde_json::from_str(&data)?;
-        let address_value = v
-            .get("address")
-            .ok_or_else(|| CacheError::JsonError(SerdeError::custom("Address field not found")))?;
-        serde_json::from_value(address_value.clone()).map_err(CacheError::JsonError)
+        Self::read_from_dir(&default_cache_dir(), &Cluster::default())
+    }
+
+    pub fn read_from_dir(dir: &Path, cluster: &Cluster) -> Result<Self, CacheError> {
+        read_addresses(&cache_file(dir, cluster, "market.json"))
+    }
+
+    pub fn write_to_cache(&self) -> Result<(), CacheError> {
+        self.write_to_dir(&default_cache_dir(), &Cluster::default())
+    }
+
+    pub fn write_to_dir(&self, dir: &Path, cluster: &Cluster) -> Result<(), CacheError> {
+        write_addresses(&cache_file(dir, cluster, "market.json"), self)
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Pool {
     pub program_id: String,
@@ -86,29 +82,73 @@ pub struct Pool {
 
 impl Pool {
     pub fn read_from_cache(expected_market_id: &str) -> Result<Self, CacheError> {
-        let path = std::env::current_dir()
-            .expect("Unable to get current directory")
-            .join(format!("{}{}pool.json", CACHE_DIR, PREFIX));
-        if !path.exists() {
-            error!("File not found at path: {}", path.to_string_lossy());
-            return Err(CacheError::FileNotFound(
-                path.to_string_lossy().into_owned(),
-            ));
-        }
-        let data = fs::read_to_string(&path).map_err(|_| {
-            error!("Unable to read file: {}", path.to_string_lossy());
-            CacheError::ReadError(path.to_string_lossy().into_owned())
-        })?;
-        let v: Value = serde_json::from_str(&data)?;
-        let address_value = v
-            .get("address")
-            .ok_or_else(|| CacheError::JsonError(SerdeError::custom("Address field not found")))?;
-        let pool: Pool = serde_json::from_value(address_value.clone())?;
+        Self::read_from_dir(
+            &default_cache_dir(),
+            &Cluster::default(),
+            expected_market_id,
+        )
+    }
+
+    pub fn read_from_dir(
+        dir: &Path,
+        cluster: &Cluster,
+        expected_market_id: &str,
+    ) -> Result<Self, CacheError> {
+        let pool: Pool = read_addresses(&cache_file(dir, cluster, "pool.json"))?;
         if pool.market_id != expected_market_id {
             return Err(CacheError::CacheIdMismatch);
         }
         Ok(pool)
     }
+
+    pub fn write_to_cache(&self) -> Result<(), CacheError> {
+        self.write_to_dir(&default_cache_dir(), &Cluster::default())
+    }
+
+    pub fn write_to_dir(&self, dir: &Path, cluster: &Cluster) -> Result<(), CacheError> {
+        write_addresses(&cache_file(dir, cluster, "pool.json"), self)
+    }
+}
+
+// The cache shared with the TS tests, relative to the client crate
+pub fn default_cache_dir() -> PathBuf {
+    std::env::current_dir()
+        .expect("Unable to get current directory")
+        .join(CACHE_DIR)
+}
+
+// Same naming as the TS helpers, e.g. `devnet_pool.json`
+fn cache_file(dir: &Path, cluster: &Cluster, file_name: &str) -> PathBuf {
+    dir.join(format!("{}{}", cluster.cache_prefix(), file_name))
+}
+
+fn read_addresses<T: DeserializeOwned>(path: &Path) -> Result<T, CacheError> {
+    if !path.exists() {
+        error!("File not found at path: {}", path.to_string_lossy());
+        return Err(CacheError::FileNotFound(
+            path.to_string_lossy().into_owned(),
+        ));
+    }
+    let data = fs::read_to_string(path).map_err(|_| {
+        error!("Unable to read file: {}", path.to_string_lossy());
+        CacheError::ReadError(path.to_string_lossy().into_owned())
+    })?;
+    let v: Value = serde_json::from_str(&data)?;
+    let address_value = v
+        .get("address")
+        .ok_or_else(|| CacheError::JsonError(SerdeError::custom("Address field not found")))?;
+    serde_json::from_value(address_value.clone()).map_err(CacheError::JsonError)
+}
+
+// Same layout as the TS helpers write, the addresses live under an `address` key
+fn write_addresses<T: Serialize>(path: &Path, addresses: &T) -> Result<(), CacheError> {
+    let data = serde_json::to_string_pretty(&serde_json::json!({ "address": addresses }))?;
+    fs::write(path, data).map_err(|_| {
+        error!("Unable to write file: {}", path.to_string_lossy());
+        CacheError::WriteError(path.to_string_lossy().into_owned())
+    })?;
+    debug!("Saved to cache: {}", path.to_string_lossy());
+    Ok(())
 }
 
 #[cfg(test)]
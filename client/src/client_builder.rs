@@ -0,0 +1,295 @@
+use raydium_library::common;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+use std::env;
+use std::path::PathBuf;
+use std::rc::Rc;
+use thiserror::Error;
+
+use crate::backend::{RpcBackend, TransactionBackend};
+use crate::cache::default_cache_dir;
+use crate::client_helper::ClientHelper;
+use crate::cluster::Cluster;
+use crate::compute_budget::ComputeBudgetConfig;
+use crate::confirmation::ConfirmationConfig;
+
+// Where the user wallet comes from
+#[derive(Debug)]
+pub enum KeypairSource {
+    Keypair(Keypair),
+    File(PathBuf),
+    // $HOME/.config/solana/id.json, like the solana CLI
+    DefaultWallet,
+}
+
+#[derive(Debug, Error)]
+pub enum ClientBuilderError {
+    #[error("No keypair source was given")]
+    MissingKeypair,
+
+    #[error("Unable to read keypair {path}: {reason}")]
+    Keypair { path: PathBuf, reason: String },
+
+    #[error("Invalid {kind} url: {url}")]
+    InvalidUrl { kind: &'static str, url: String },
+
+    #[error("{program} is not the AMM v4 program on {cluster}")]
+    UnknownAmmProgram { cluster: String, program: Pubkey },
+
+    #[error("{program} is not the OpenBook program on {cluster}")]
+    UnknownMarketProgram { cluster: String, program: Pubkey },
+
+    #[error("Cache directory not found: {0}")]
+    CacheDir(PathBuf),
+}
+
+// Fallible ClientHelper construction, nothing is read from the environment unless asked for.
+// Urls, programs and commitment default to the cluster profile.
+#[derive(Default)]
+pub struct ClientHelperBuilder {
+    cluster: Cluster,
+    keypair: Option<KeypairSource>,
+    // sponsor paying fees and rent on behalf of the keypair, defaults to the keypair itself
+    fee_payer: Option<KeypairSource>,
+    rpc_url: Option<String>,
+    websocket_url: Option<String>,
+    commitment: Option<CommitmentConfig>,
+    programs: Option<(Pubkey, Pubkey)>,
+    cache_dir: Option<PathBuf>,
+    compute_budget: ComputeBudgetConfig,
+}
+
+impl ClientHelperBuilder {
+    pub fn cluster(mut self, cluster: Cluster) -> Self {
+        self.cluster = cluster;
+        self
+    }
+
+    pub fn keypair(mut self, source: KeypairSource) -> Self {
+        self.keypair = Some(source);
+        self
+    }
+
+    pub fn fee_payer(mut self, source: KeypairSource) -> Self {
+        self.fee_payer = Some(source);
+        self
+    }
+
+    pub fn rpc_url(mut self, url: impl Into<String>) -> Self {
+        self.rpc_url = Some(url.into());
+        self
+    }
+
+    pub fn websocket_url(mut self, url: impl Into<String>) -> Self {
+        self.websocket_url = Some(url.into());
+        self
+    }
+
+    pub fn commitment(mut self, commitment: CommitmentConfig) -> Self {
+        self.commitment = Some(commitment);
+        self
+    }
+
+    pub fn programs(mut self, amm_program: Pubkey, market_program: Pubkey) -> Self {
+        self.programs = Some((amm_program, market_program));
+        self
+    }
+
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    pub fn compute_budget(mut self, compute_budget: ComputeBudgetConfig) -> Self {
+        self.compute_budget = compute_budget;
+        self
+    }
+
+    pub fn build(self) -> Result<ClientHelper, ClientBuilderError> {
+        let cluster = self.cluster;
+        let rpc_url = self.rpc_url.unwrap_or_else(|| cluster.rpc_url());
+        let websocket_url = self
+            .websocket_url
+            .unwrap_or_else(|| cluster.websocket_url());
+        check_url("RPC", &rpc_url, &["http://", "https://"])?;
+        check_url("websocket", &websocket_url, &["ws://", "wss://"])?;
+        let (amm_program, market_program) = self
+            .programs
+            .unwrap_or_else(|| (cluster.amm_program(), cluster.market_program()));
+        if cluster.checks_programs() && amm_program != cluster.amm_program() {
+            return Err(ClientBuilderError::UnknownAmmProgram {
+                cluster: cluster.to_string(),
+                program: amm_program,
+            });
+        }
+        if cluster.checks_programs() && market_program != cluster.market_program() {
+            return Err(ClientBuilderError::UnknownMarketProgram {
+                cluster: cluster.to_string(),
+                program: market_program,
+            });
+        }
+        let cache_dir = self.cache_dir.unwrap_or_else(default_cache_dir);
+        if !cache_dir.is_dir() {
+            return Err(ClientBuilderError::CacheDir(cache_dir));
+        }
+
+        let mut config = common::CommonConfig::default();
+        let keypair = self.keypair.ok_or(ClientBuilderError::MissingKeypair)?;
+        let user_keypair = Rc::new(load_keypair(&mut config, keypair)?);
+        // the config wallet stays the owner's, the sponsor only signs
+        let payer = match self.fee_payer {
+            Some(source) => Rc::new(load_keypair(&mut common::CommonConfig::default(), source)?)
+                as Rc<dyn Signer>,
+            None => Rc::clone(&user_keypair) as Rc<dyn Signer>,
+        };
+        config.set_cluster(&rpc_url, &websocket_url);
+
+        let confirmation = ConfirmationConfig {
+            commitment: self.commitment.unwrap_or_else(|| cluster.commitment()),
+            websocket_url: Some(websocket_url),
+            ..ConfirmationConfig::default()
+        };
+        // reads use the same commitment the transactions are confirmed with
+        let backend = Rc::new(RpcBackend::new(Rc::new(RpcClient::new_with_commitment(
+            rpc_url,
+            confirmation.commitment,
+        )))) as Rc<dyn TransactionBackend>;
+        Ok(ClientHelper {
+            backend,
+            user_keypair,
+            payer: Rc::clone(&payer),
+            signing_keypairs: vec![payer],
+            config,
+            confirmation,
+            compute_budget: self.compute_budget,
+            cluster,
+            amm_program,
+            market_program,
+            cache_dir,
+        })
+    }
+}
+
+fn check_url(kind: &'static str, url: &str, schemes: &[&str]) -> Result<(), ClientBuilderError> {
+    let host = schemes.iter().find_map(|scheme| url.strip_prefix(scheme));
+    match host {
+        Some(host) if !host.is_empty() => Ok(()),
+        _ => Err(ClientBuilderError::InvalidUrl {
+            kind,
+            url: url.to_string(),
+        }),
+    }
+}
+
+fn load_keypair(
+    config: &mut common::CommonConfig,
+    source: KeypairSource,
+) -> Result<Keypair, ClientBuilderError> {
+    match source {
+        KeypairSource::Keypair(keypair) => Ok(keypair),
+        KeypairSource::File(path) => read_keypair(config, path),
+        KeypairSource::DefaultWallet => read_keypair(config, default_wallet_path()?),
+    }
+}
+
+fn read_keypair(
+    config: &mut common::CommonConfig,
+    path: PathBuf,
+) -> Result<Keypair, ClientBuilderError> {
+    let keypair = read_keypair_file(&path).map_err(|e| ClientBuilderError::Keypair {
+        path: path.clone(),
+        reason: e.to_string(),
+    })?;
+    config.set_wallet(&path.to_string_lossy());
+    Ok(keypair)
+}
+
+fn default_wallet_path() -> Result<PathBuf, ClientBuilderError> {
+    let home_dir = env::var("HOME").map_err(|e| ClientBuilderError::Keypair {
+        path: PathBuf::from("~/.config/solana/id.json"),
+        reason: e.to_string(),
+    })?;
+    Ok(PathBuf::from(home_dir).join(".config/solana/id.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client_helper::WalletKeys;
+
+    fn builder() -> ClientHelperBuilder {
+        ClientHelper::builder()
+            .keypair(KeypairSource::Keypair(Keypair::new()))
+            .rpc_url("http://localhost:8899")
+            .cache_dir(std::env::temp_dir())
+    }
+
+    #[test]
+    fn test_builder_validates_configuration() {
+        let client = builder()
+            .websocket_url("ws://localhost:8900")
+            .commitment(CommitmentConfig::finalized())
+            .build()
+            .unwrap();
+        assert_eq!(
+            client.confirmation.commitment,
+            CommitmentConfig::finalized()
+        );
+        assert_eq!(client.cache_dir, std::env::temp_dir());
+        assert_eq!(client.cluster, Cluster::Devnet);
+        assert_eq!(client.amm_program, Cluster::Devnet.amm_program());
+        assert_eq!(client.payer(), client.owner());
+
+        let sponsor = Keypair::new();
+        let sponsor_pubkey = sponsor.pubkey();
+        let sponsored = builder()
+            .fee_payer(KeypairSource::Keypair(sponsor))
+            .build()
+            .unwrap();
+        assert_eq!(sponsored.payer(), sponsor_pubkey);
+        assert_ne!(sponsored.owner(), sponsor_pubkey);
+
+        let mainnet = builder().cluster(Cluster::MainnetBeta).build().unwrap();
+        assert_eq!(
+            mainnet.market_program,
+            Cluster::MainnetBeta.market_program()
+        );
+        assert_eq!(
+            mainnet.confirmation.commitment,
+            CommitmentConfig::finalized()
+        );
+
+        assert!(matches!(
+            builder().rpc_url("localhost:8899").build(),
+            Err(ClientBuilderError::InvalidUrl { kind: "RPC", .. })
+        ));
+        assert!(matches!(
+            builder().websocket_url("http://localhost:8900").build(),
+            Err(ClientBuilderError::InvalidUrl {
+                kind: "websocket",
+                ..
+            })
+        ));
+        assert!(matches!(
+            builder()
+                .programs(
+                    Cluster::Devnet.amm_program(),
+                    Cluster::MainnetBeta.market_program()
+                )
+                .build(),
+            Err(ClientBuilderError::UnknownMarketProgram { .. })
+        ));
+        assert!(matches!(
+            builder()
+                .keypair(KeypairSource::File(PathBuf::from("/nonexistent/id.json")))
+                .build(),
+            Err(ClientBuilderError::Keypair { .. })
+        ));
+        assert!(matches!(
+            builder().cache_dir("/nonexistent").build(),
+            Err(ClientBuilderError::CacheDir(_))
+        ));
+    }
+}
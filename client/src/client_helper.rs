@@ -1,59 +1,143 @@
 use anyhow::{Context, Result};
-use log::{debug, error, info};
+use log::{debug, info};
 use raydium_library::common;
 use solana_client::rpc_client::RpcClient;
 use solana_client::rpc_response::RpcSimulateTransactionResult;
-use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::account::Account;
+use solana_sdk::hash::Hash;
 use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
 use solana_sdk::program_pack::Pack;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::signer::SignerError;
+use solana_sdk::transaction::{Transaction, TransactionError};
 use spl_associated_token_account::get_associated_token_address;
 use spl_token::state::Account as TokenAccount;
-use std::env;
-use std::error::Error;
-use std::io::Write;
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
 
-use crate::config::{get_cluster_urls, WAIT_TIME_AFTER_TRANSACTION};
+use crate::backend::{RpcBackend, TransactionBackend};
+use crate::client_builder::{ClientHelperBuilder, KeypairSource};
+use crate::cluster::Cluster;
+use crate::compute_budget::ComputeBudgetConfig;
+use crate::config::{get_cluster, get_cluster_urls};
+use crate::confirmation::{
+    confirm_transaction, ConfirmationConfig, ConfirmationError, ConfirmationReport,
+};
+use crate::program_error::PoolProgramError;
+use crate::transaction_builder::{confirmation_error, sign_message, TransactionBuilder};
 
 pub struct ClientHelper {
-    pub client: Rc<RpcClient>,
+    pub backend: Rc<dyn TransactionBackend>,
     pub user_keypair: Rc<Keypair>,
     pub payer: Rc<dyn Signer>,
     pub signing_keypairs: Vec<Rc<dyn Signer>>,
     pub config: common::types::CommonConfig,
+    pub confirmation: ConfirmationConfig,
+    // ComputeBudget instructions added by process_transaction, none by default
+    pub compute_budget: ComputeBudgetConfig,
+    pub cluster: Cluster,
+    pub amm_program: Pubkey,
+    pub market_program: Pubkey,
+    // where created markets and pools are saved
+    pub cache_dir: PathBuf,
 }
 
 impl Default for ClientHelper {
+    // Cluster from SOLANA_CLUSTER and wallet from $HOME/.config/solana/id.json,
+    // panics if the configuration is invalid
     fn default() -> Self {
-        let mut config = common::CommonConfig::default();
-        #[cfg(feature = "devnet")] {
-            let (cluster_url, websocket_url) = get_cluster_urls();
-            config.set_cluster(&cluster_url, &websocket_url);
-        }
-        println!("cluster_url: {}", config.cluster().url());
-        config.set_wallet(&get_default_wallet_path());
-        debug!("\nConfig: {:?}\n\n", config);
-        let client = Rc::new(RpcClient::new(config.cluster().url()));
-        let user_keypair = Rc::new(common::utils::read_keypair_file(&config.wallet()).unwrap());
-        let fee_payer = Rc::clone(&user_keypair) as Rc<dyn Signer>;
-        let signing_keypairs = vec![Rc::clone(&fee_payer)];
+        let cluster = get_cluster().expect("Invalid SOLANA_CLUSTER");
+        let (cluster_url, websocket_url) = get_cluster_urls(&cluster);
+        let client = ClientHelper::builder()
+            .cluster(cluster)
+            .keypair(KeypairSource::DefaultWallet)
+            .rpc_url(cluster_url)
+            .websocket_url(websocket_url)
+            .build()
+            .expect("Invalid default ClientHelper configuration");
+        debug!("\nConfig: {:?}\n\n", client.config);
+        client
+    }
+}
 
-        Self {
-            client,
-            user_keypair,
-            payer: fee_payer,
-            signing_keypairs,
-            config,
+impl ClientHelper {
+    pub fn builder() -> ClientHelperBuilder {
+        ClientHelperBuilder::default()
+    }
+}
+
+// Wallets the instruction builders need, shared by the blocking and the async client
+pub trait WalletKeys {
+    // holds the tokens and signs the pool instructions
+    fn owner(&self) -> Pubkey;
+    // pays the fees and the rent of the accounts created along the way
+    fn payer(&self) -> Pubkey;
+}
+
+impl WalletKeys for ClientHelper {
+    fn owner(&self) -> Pubkey {
+        self.user_keypair.pubkey()
+    }
+
+    fn payer(&self) -> Pubkey {
+        self.payer.pubkey()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ClientHelperError {
+    #[error("Failed to build transaction: {0}")]
+    Build(#[from] SignerError),
+
+    #[error("Simulation failed: {err}")]
+    Simulation {
+        err: TransactionError,
+        logs: Vec<String>,
+        // custom error raised by the AMM, the token program or any other failing program
+        program_error: Option<PoolProgramError>,
+    },
+
+    #[error("Failed to send transaction: {0}")]
+    Send(anyhow::Error),
+
+    #[error("Blockhash expired before the transaction landed")]
+    BlockhashExpired { signature: Option<Signature> },
+
+    #[error("Transaction {signature} not confirmed after {elapsed:?}")]
+    ConfirmationTimeout {
+        signature: Signature,
+        elapsed: Duration,
+    },
+
+    #[error("Transaction {signature} failed: {err}")]
+    TransactionFailed {
+        signature: Signature,
+        err: TransactionError,
+    },
+
+    #[error(transparent)]
+    Backend(#[from] anyhow::Error),
+}
+
+impl ClientHelperError {
+    // Lets callers react to a program failure, e.g. retry with a wider slippage
+    pub fn program_error(&self) -> Option<&PoolProgramError> {
+        match self {
+            Self::Simulation { program_error, .. } => program_error.as_ref(),
+            _ => None,
         }
     }
 }
 
+#[derive(Debug)]
 pub struct ClientHelperTxResult {
-    pub simulation_result: Option<RpcSimulateTransactionResult>,
+    pub simulation_result: RpcSimulateTransactionResult,
     pub signature: Option<Signature>,
+    pub confirmation: Option<ConfirmationReport>,
 }
 
 impl ClientHelper {
@@ -61,55 +145,87 @@ impl ClientHelper {
         &self,
         instructions: &[Instruction],
         dryrun: bool,
-    ) -> ClientHelperTxResult {
-        let payer_pubkey = self.payer.pubkey();
-        let signing_keypairs_refs: Vec<&dyn Signer> =
-            self.signing_keypairs.iter().map(|kp| kp.as_ref()).collect();
-        let txn = common::build_txn(
-            &self.client,
+    ) -> Result<ClientHelperTxResult, ClientHelperError> {
+        let blockhash = self.backend.get_latest_blockhash()?;
+        let mut builder = TransactionBuilder::new(
             instructions,
-            &payer_pubkey,
-            &signing_keypairs_refs,
-        )
-        .unwrap();
+            self.payer.pubkey(),
+            &self.compute_budget,
+            self.amm_program,
+        );
+        let recent_fees = match builder.fee_accounts() {
+            Some(accounts) => self.backend.get_recent_prioritization_fees(&accounts)?,
+            None => Vec::new(),
+        };
+        builder = builder.with_recent_fees(&recent_fees);
+        let mut txn = self.sign(builder.message(), blockhash)?;
 
         // Always simulate the transaction
-        let sim_result = match common::simulate_transaction(
-            &self.client,
-            &txn,
-            false,
-            CommitmentConfig::confirmed(),
-        ) {
-            Ok(result) => Some(result.value),
-            Err(e) => {
-                error!("\nSimulation Error: {:?}\n\n", e);
-                None
-            }
-        };
-        debug!("\nSimulation Result: {:#?}\n\n", sim_result);
-
-        // Match on the simulation result to handle success or failure
-        let signature = if !dryrun {
-            // Proceed to send the transaction if not in dryrun mode
-            let sig = common::send_txn(&self.client, &txn, true);
-            debug!("\nTransaction Result: {:#?}\n\n", sig);
-
-            // Match on the transaction result to handle success or failure
-            match sig {
-                Ok(value) => Some(value),
-                Err(ref error) => {
-                    error!("\nTransaction Error: {:?}\n\n", error);
-                    None
-                }
-            }
-        } else {
-            None
-        };
+        let sim_result = self.backend.simulate_transaction(&txn)?;
+        if builder.simulated(&sim_result)? {
+            txn = self.sign(builder.message(), blockhash)?;
+        }
+
+        if dryrun {
+            return Ok(ClientHelperTxResult {
+                simulation_result: sim_result,
+                signature: None,
+                confirmation: None,
+            });
+        }
+
+        let sent_at = Instant::now();
+        let signature = self
+            .backend
+            .send_transaction(&txn)
+            .map_err(ClientHelperError::Send)?;
+        debug!(
+            "\nTransaction {} sent in {:?}\n\n",
+            signature,
+            sent_at.elapsed()
+        );
 
-        ClientHelperTxResult {
+        // Only report the signature once it reached the configured commitment
+        let confirmation = confirm_transaction(
+            self.backend.as_ref(),
+            &signature,
+            &self.confirmation,
+            sent_at,
+        )
+        .map_err(|e| {
+            let blockhash_valid = matches!(e, ConfirmationError::Timeout { .. })
+                .then(|| self.backend.is_blockhash_valid(&blockhash).ok())
+                .flatten();
+            confirmation_error(signature, e, blockhash_valid)
+        })?;
+
+        Ok(ClientHelperTxResult {
             simulation_result: sim_result,
+            signature: Some(signature),
+            confirmation: Some(confirmation),
+        })
+    }
+
+    fn sign(&self, message: Message, blockhash: Hash) -> Result<Transaction, SignerError> {
+        let candidates = [
+            self.payer.as_ref(),
+            self.user_keypair.as_ref() as &dyn Signer,
+        ]
+        .into_iter()
+        .chain(self.signing_keypairs.iter().map(|kp| kp.as_ref()));
+        sign_message(message, candidates, blockhash)
+    }
+
+    pub fn confirm_transaction(
+        &self,
+        signature: &Signature,
+    ) -> Result<ConfirmationReport, ConfirmationError> {
+        confirm_transaction(
+            self.backend.as_ref(),
             signature,
-        }
+            &self.confirmation,
+            Instant::now(),
+        )
     }
 
     pub fn derive_ata_and_fetch_balance(
@@ -118,37 +234,177 @@ impl ClientHelper {
         mint_address: &Pubkey,
     ) -> Result<u64> {
         let ata = get_associated_token_address(wallet_address, mint_address);
-        let account = self.client.get_account(&ata)?;
+        let account = self.get_account(&ata)?;
         let token_account = TokenAccount::unpack(&account.data)?;
         debug!("Token Account: {:?}", token_account);
         Ok(token_account.amount)
     }
 
     pub fn fetch_token_balance(&self, mint_address: &Pubkey) -> Result<u64> {
-        let account = self.client.get_account(mint_address)?;
+        let account = self.get_account(mint_address)?;
         let token_account = TokenAccount::unpack(&account.data)?;
         debug!("Token Account: {:?}", token_account);
         Ok(token_account.amount)
     }
 
-    // yes using timing is not ideal, will refactor later
-    pub fn tests_wait_for_confirmation(&self) {
-        let mut remaining_time = WAIT_TIME_AFTER_TRANSACTION;
-        info!("Please wait a bit to stabilize the tests and all the accounts fetching");
-        while remaining_time > 0 {
-            print!("\rWaiting for {} seconds after transaction", remaining_time);
-            std::io::stdout().flush().unwrap();
-            std::thread::sleep(std::time::Duration::from_secs(1));
-            remaining_time -= 1;
-        }
-        println!("\n");
+    pub fn get_account(&self, address: &Pubkey) -> Result<Account> {
+        self.backend
+            .get_account(address)?
+            .ok_or_else(|| anyhow::anyhow!("Account not found: {}", address))
     }
 }
 
-fn get_default_wallet_path() -> String {
-    let home_dir = env::var("HOME").expect("Could not find home directory");
-    PathBuf::from(home_dir)
-        .join(".config/solana/id.json")
-        .to_string_lossy()
-        .into_owned()
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use crate::compute_budget::PriorityFeeStrategy;
+    use raydium_amm::error::AmmError;
+    use raydium_puppet::constants::{AMM_V4_PROGRAM_IDS, OPENBOOK_PROGRAM_IDS};
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use solana_sdk::instruction::InstructionError;
+    use solana_sdk::system_instruction;
+
+    pub(crate) fn mock_client(backend: Rc<MockBackend>) -> ClientHelper {
+        let user_keypair = Rc::new(Keypair::new());
+        let fee_payer = Rc::clone(&user_keypair) as Rc<dyn Signer>;
+        ClientHelper {
+            backend,
+            user_keypair,
+            payer: Rc::clone(&fee_payer),
+            signing_keypairs: vec![fee_payer],
+            config: common::CommonConfig::default(),
+            confirmation: ConfirmationConfig::default(),
+            compute_budget: ComputeBudgetConfig::default(),
+            cluster: Cluster::Localnet,
+            amm_program: AMM_V4_PROGRAM_IDS[1],
+            market_program: OPENBOOK_PROGRAM_IDS[1],
+            cache_dir: std::env::temp_dir(),
+        }
+    }
+
+    fn transfer_ix(client: &ClientHelper) -> Instruction {
+        system_instruction::transfer(&client.payer.pubkey(), &Pubkey::new_unique(), 1)
+    }
+
+    #[test]
+    fn test_process_transaction_sends_and_confirms() {
+        let backend = Rc::new(MockBackend::default());
+        let client = mock_client(Rc::clone(&backend));
+
+        let dryrun = client
+            .process_transaction(&[transfer_ix(&client)], true)
+            .unwrap();
+        assert!(dryrun.signature.is_none());
+        assert!(backend.sent_transactions().is_empty());
+
+        let result = client
+            .process_transaction(&[transfer_ix(&client)], false)
+            .unwrap();
+        assert!(result.signature.is_some());
+        assert!(result.confirmation.is_some());
+        assert_eq!(backend.sent_transactions().len(), 1);
+    }
+
+    #[test]
+    fn test_process_transaction_with_sponsor() {
+        let backend = Rc::new(MockBackend::default());
+        let mut client = mock_client(Rc::clone(&backend));
+        let sponsor = Rc::new(Keypair::new()) as Rc<dyn Signer>;
+        client.payer = Rc::clone(&sponsor);
+        client.signing_keypairs = vec![sponsor];
+
+        // the owner moves its funds, the sponsor only pays the fee
+        let owner_transfer =
+            system_instruction::transfer(&client.owner(), &Pubkey::new_unique(), 1);
+        client
+            .process_transaction(&[owner_transfer], false)
+            .unwrap();
+        // the owner is not required and must not sign
+        client
+            .process_transaction(&[transfer_ix(&client)], false)
+            .unwrap();
+
+        let sent = backend.sent_transactions();
+        assert_eq!(sent[0].message.account_keys[0], client.payer());
+        assert_eq!(sent[0].message.account_keys[1], client.owner());
+        assert_eq!(sent[0].signatures.len(), 2);
+        assert!(sent[0].verify().is_ok());
+        assert_eq!(sent[1].signatures.len(), 1);
+        assert!(sent[1].verify().is_ok());
+    }
+
+    #[test]
+    fn test_process_transaction_with_compute_budget() {
+        let backend = Rc::new(MockBackend::default());
+        backend.set_simulation_result(RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(Vec::new()),
+            accounts: None,
+            units_consumed: Some(100_000),
+            return_data: None,
+        });
+        backend.set_prioritization_fees(vec![0, 1_000, 5_000, 20_000]);
+        let mut client = mock_client(Rc::clone(&backend));
+        client.compute_budget = ComputeBudgetConfig {
+            simulate_unit_limit: true,
+            priority_fee: Some(PriorityFeeStrategy::RecentPercentile(75).capped(2_000)),
+            ..ComputeBudgetConfig::default()
+        };
+
+        client
+            .process_transaction(&[transfer_ix(&client)], false)
+            .unwrap();
+
+        // the simulated units plus 10% and the capped 75th percentile, ahead of the transfer
+        let sent = backend.sent_transactions();
+        let data: Vec<_> = sent[0]
+            .message
+            .instructions
+            .iter()
+            .map(|ix| ix.data.clone())
+            .collect();
+        assert_eq!(
+            data,
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(110_000).data,
+                ComputeBudgetInstruction::set_compute_unit_price(2_000).data,
+                transfer_ix(&client).data,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_process_transaction_simulation_failure() {
+        let backend = Rc::new(MockBackend::default());
+        let amm_program = AMM_V4_PROGRAM_IDS[1];
+        backend.set_simulation_result(RpcSimulateTransactionResult {
+            err: Some(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(AmmError::ExceededSlippage as u32),
+            )),
+            logs: Some(vec![format!(
+                "Program {} failed: custom program error: {:#x}",
+                amm_program,
+                AmmError::ExceededSlippage as u32
+            )]),
+            accounts: None,
+            units_consumed: None,
+            return_data: None,
+        });
+        let client = mock_client(Rc::clone(&backend));
+
+        let result = client.process_transaction(&[transfer_ix(&client)], false);
+        assert!(matches!(
+            result,
+            Err(ClientHelperError::Simulation {
+                program_error: Some(PoolProgramError::ExceededSlippage),
+                ..
+            })
+        ));
+        assert!(
+            backend.sent_transactions().is_empty(),
+            "A failed simulation must not be sent"
+        );
+    }
 }
@@ -0,0 +1,206 @@
+use raydium_puppet::constants::{AMM_V4_PROGRAM_IDS, OPENBOOK_PROGRAM_IDS};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+// Which cluster the client talks to, picked at runtime so one binary can serve all of them
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Cluster {
+    // solana-test-validator with the devnet programs cloned
+    Localnet,
+    // the cluster the cache and the TS tests target
+    #[default]
+    Devnet,
+    MainnetBeta,
+    Custom(CustomCluster),
+}
+
+// Any other deployment, e.g. a private RPC or a validator with its own program builds
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CustomCluster {
+    // names the cache files, `<name>_market.json`
+    pub name: String,
+    pub rpc_url: String,
+    pub websocket_url: String,
+    pub amm_program: Pubkey,
+    pub market_program: Pubkey,
+}
+
+impl CustomCluster {
+    // The devnet programs on an RPC of its own, the websocket url is derived like the
+    // solana CLI does: ws(s) scheme and the next port when one is given
+    fn from_rpc_url(rpc_url: &str) -> Option<Self> {
+        let (ws_scheme, rest) = if let Some(rest) = rpc_url.strip_prefix("https://") {
+            ("wss://", rest)
+        } else {
+            ("ws://", rpc_url.strip_prefix("http://")?)
+        };
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if authority.is_empty() {
+            return None;
+        }
+        let authority = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse::<u16>().ok()?.checked_add(1)?;
+                format!("{}:{}", host, port)
+            }
+            None => authority.to_string(),
+        };
+        Some(Self {
+            name: "custom".to_string(),
+            rpc_url: rpc_url.to_string(),
+            websocket_url: format!("{}{}{}", ws_scheme, authority, path),
+            amm_program: Cluster::Devnet.amm_program(),
+            market_program: Cluster::Devnet.market_program(),
+        })
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("Unknown cluster {0}, expected localnet, devnet, mainnet-beta or custom:<url>")]
+pub struct ParseClusterError(String);
+
+impl Cluster {
+    pub fn name(&self) -> &str {
+        match self {
+            Cluster::Localnet => "localnet",
+            Cluster::Devnet => "devnet",
+            Cluster::MainnetBeta => "mainnet-beta",
+            Cluster::Custom(custom) => &custom.name,
+        }
+    }
+
+    pub fn rpc_url(&self) -> String {
+        match self {
+            Cluster::Localnet => "http://127.0.0.1:8899".to_string(),
+            Cluster::Devnet => "https://api.devnet.solana.com".to_string(),
+            Cluster::MainnetBeta => "https://api.mainnet-beta.solana.com".to_string(),
+            Cluster::Custom(custom) => custom.rpc_url.clone(),
+        }
+    }
+
+    pub fn websocket_url(&self) -> String {
+        match self {
+            Cluster::Localnet => "ws://127.0.0.1:8900".to_string(),
+            Cluster::Devnet => "wss://api.devnet.solana.com".to_string(),
+            Cluster::MainnetBeta => "wss://api.mainnet-beta.solana.com".to_string(),
+            Cluster::Custom(custom) => custom.websocket_url.clone(),
+        }
+    }
+
+    pub fn amm_program(&self) -> Pubkey {
+        match self {
+            Cluster::MainnetBeta => AMM_V4_PROGRAM_IDS[0],
+            Cluster::Localnet | Cluster::Devnet => AMM_V4_PROGRAM_IDS[1],
+            Cluster::Custom(custom) => custom.amm_program,
+        }
+    }
+
+    pub fn market_program(&self) -> Pubkey {
+        match self {
+            Cluster::MainnetBeta => OPENBOOK_PROGRAM_IDS[0],
+            Cluster::Localnet | Cluster::Devnet => OPENBOOK_PROGRAM_IDS[1],
+            Cluster::Custom(custom) => custom.market_program,
+        }
+    }
+
+    // Mainnet keeps the TS helpers' unprefixed file names
+    pub fn cache_prefix(&self) -> String {
+        match self {
+            Cluster::MainnetBeta => String::new(),
+            other => format!("{}_", other.name()),
+        }
+    }
+
+    // Real funds are only reported once finalized
+    pub fn commitment(&self) -> CommitmentConfig {
+        match self {
+            Cluster::MainnetBeta => CommitmentConfig::finalized(),
+            _ => CommitmentConfig::confirmed(),
+        }
+    }
+
+    // Public clusters only accept their own program ids, custom ones take whatever they are given
+    pub fn checks_programs(&self) -> bool {
+        !matches!(self, Cluster::Custom(_))
+    }
+}
+
+impl FromStr for Cluster {
+    type Err = ParseClusterError;
+
+    // Same monikers as the solana CLI `--url` flag, `custom:<url>` for any other RPC
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(rpc_url) = s.strip_prefix("custom:") {
+            return CustomCluster::from_rpc_url(rpc_url)
+                .map(Cluster::Custom)
+                .ok_or_else(|| ParseClusterError(s.to_string()));
+        }
+        match s.to_lowercase().as_str() {
+            "localnet" | "localhost" | "l" => Ok(Cluster::Localnet),
+            "devnet" | "d" => Ok(Cluster::Devnet),
+            "mainnet-beta" | "mainnet" | "m" => Ok(Cluster::MainnetBeta),
+            _ => Err(ParseClusterError(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Cluster {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cluster_profiles() {
+        assert_eq!("d".parse::<Cluster>().unwrap(), Cluster::Devnet);
+        assert_eq!(
+            "mainnet-beta".parse::<Cluster>().unwrap(),
+            Cluster::MainnetBeta
+        );
+        assert!("testnet".parse::<Cluster>().is_err());
+
+        assert_eq!(Cluster::Devnet.cache_prefix(), "devnet_");
+        assert_eq!(Cluster::MainnetBeta.cache_prefix(), "");
+        assert_eq!(
+            Cluster::Localnet.amm_program(),
+            Cluster::Devnet.amm_program()
+        );
+        assert_ne!(
+            Cluster::MainnetBeta.amm_program(),
+            Cluster::Devnet.amm_program()
+        );
+        assert_eq!(
+            Cluster::MainnetBeta.commitment(),
+            CommitmentConfig::finalized()
+        );
+    }
+
+    #[test]
+    fn test_custom_cluster_from_url() {
+        let cluster = "custom:http://10.0.0.1:8899".parse::<Cluster>().unwrap();
+        assert_eq!(cluster.rpc_url(), "http://10.0.0.1:8899");
+        assert_eq!(cluster.websocket_url(), "ws://10.0.0.1:8900");
+        assert_eq!(cluster.cache_prefix(), "custom_");
+        assert_eq!(cluster.amm_program(), Cluster::Devnet.amm_program());
+        assert!(!cluster.checks_programs());
+
+        // the url keeps its case, only the moniker is case insensitive
+        let cluster = "custom:https://RPC.example.com/Key"
+            .parse::<Cluster>()
+            .unwrap();
+        assert_eq!(cluster.websocket_url(), "wss://RPC.example.com/Key");
+
+        assert!("custom:".parse::<Cluster>().is_err());
+        assert!("custom:ftp://rpc.example.com".parse::<Cluster>().is_err());
+        assert!("custom:http://rpc.example.com:port"
+            .parse::<Cluster>()
+            .is_err());
+    }
+}
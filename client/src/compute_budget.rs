@@ -0,0 +1,168 @@
+// ComputeBudget instructions prepended by process_transaction: a unit limit sized from the
+// simulation and a unit price, so transactions keep landing when the cluster is congested.
+use solana_sdk::compute_budget::{self, ComputeBudgetInstruction};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::config::COMPUTE_UNIT_MARGIN_PERCENT;
+
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+// getRecentPrioritizationFees accepts at most 128 accounts
+const MAX_FEE_ACCOUNTS: usize = 128;
+
+// Compute unit price, in micro-lamports
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PriorityFeeStrategy {
+    Fixed(u64),
+    // percentile (0-100) of the fees recently paid by transactions writing the same accounts
+    RecentPercentile(u8),
+    Capped {
+        strategy: Box<PriorityFeeStrategy>,
+        max: u64,
+    },
+}
+
+impl PriorityFeeStrategy {
+    pub fn capped(self, max: u64) -> Self {
+        Self::Capped {
+            strategy: Box::new(self),
+            max,
+        }
+    }
+
+    pub fn needs_recent_fees(&self) -> bool {
+        match self {
+            Self::Fixed(_) => false,
+            Self::RecentPercentile(_) => true,
+            Self::Capped { strategy, .. } => strategy.needs_recent_fees(),
+        }
+    }
+
+    pub fn price(&self, recent_fees: &[u64]) -> u64 {
+        match self {
+            Self::Fixed(price) => *price,
+            Self::RecentPercentile(rank) => percentile(recent_fees, *rank),
+            Self::Capped { strategy, max } => strategy.price(recent_fees).min(*max),
+        }
+    }
+}
+
+// Disabled by default, transactions are sent as built
+#[derive(Debug, Clone)]
+pub struct ComputeBudgetConfig {
+    // request the simulated units plus a margin instead of 200k units per instruction
+    pub simulate_unit_limit: bool,
+    pub unit_margin_percent: u64,
+    pub priority_fee: Option<PriorityFeeStrategy>,
+}
+
+impl Default for ComputeBudgetConfig {
+    fn default() -> Self {
+        Self {
+            simulate_unit_limit: false,
+            unit_margin_percent: COMPUTE_UNIT_MARGIN_PERCENT,
+            priority_fee: None,
+        }
+    }
+}
+
+impl ComputeBudgetConfig {
+    pub fn unit_limit(&self, units_consumed: u64) -> u32 {
+        let limit = units_consumed.saturating_mul(100 + self.unit_margin_percent) / 100;
+        limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+    }
+
+    // Instructions already carrying a budget are left as is, duplicates fail the transaction
+    pub fn apply(
+        &self,
+        instructions: &[Instruction],
+        unit_limit: Option<u32>,
+        unit_price: Option<u64>,
+    ) -> Vec<Instruction> {
+        if instructions
+            .iter()
+            .any(|ix| ix.program_id == compute_budget::id())
+        {
+            return instructions.to_vec();
+        }
+        let mut budgeted = Vec::with_capacity(instructions.len() + 2);
+        if let Some(units) = unit_limit {
+            budgeted.push(ComputeBudgetInstruction::set_compute_unit_limit(units));
+        }
+        if let Some(price) = unit_price {
+            budgeted.push(ComputeBudgetInstruction::set_compute_unit_price(price));
+        }
+        budgeted.extend_from_slice(instructions);
+        budgeted
+    }
+}
+
+// Nearest rank, no recent fees means no competition
+pub fn percentile(fees: &[u64], rank: u8) -> u64 {
+    if fees.is_empty() {
+        return 0;
+    }
+    let mut sorted = fees.to_vec();
+    sorted.sort_unstable();
+    let rank = rank.min(100) as usize;
+    sorted[(rank * sorted.len()).saturating_sub(1) / 100]
+}
+
+// Writable accounts other than the signers (the pool, its vaults, ...), the ones fees compete for
+pub fn fee_accounts(message: &Message) -> Vec<Pubkey> {
+    message
+        .account_keys
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| message.is_writable(*i) && !message.is_signer(*i))
+        .map(|(_, address)| *address)
+        .take(MAX_FEE_ACCOUNTS)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::system_instruction;
+
+    #[test]
+    fn test_priority_fee_strategies() {
+        let fees = [50, 10, 40, 20, 30];
+        assert_eq!(percentile(&fees, 50), 30);
+        assert_eq!(percentile(&fees, 90), 50);
+        assert_eq!(percentile(&fees, 0), 10);
+        assert_eq!(percentile(&[], 75), 0);
+
+        assert_eq!(PriorityFeeStrategy::Fixed(1_000).price(&fees), 1_000);
+        assert_eq!(PriorityFeeStrategy::RecentPercentile(75).price(&fees), 40);
+        let capped = PriorityFeeStrategy::RecentPercentile(100).capped(35);
+        assert!(capped.needs_recent_fees());
+        assert_eq!(capped.price(&fees), 35);
+        assert!(!PriorityFeeStrategy::Fixed(1).capped(35).needs_recent_fees());
+    }
+
+    #[test]
+    fn test_apply_compute_budget() {
+        let config = ComputeBudgetConfig::default();
+        assert_eq!(config.unit_limit(100_000), 110_000);
+        assert_eq!(config.unit_limit(u64::MAX), MAX_COMPUTE_UNIT_LIMIT);
+
+        let transfer =
+            system_instruction::transfer(&Pubkey::new_unique(), &Pubkey::new_unique(), 1);
+        let budgeted = config.apply(&[transfer.clone()], Some(110_000), Some(1_000));
+        assert_eq!(
+            budgeted,
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(110_000),
+                ComputeBudgetInstruction::set_compute_unit_price(1_000),
+                transfer.clone(),
+            ]
+        );
+        assert_eq!(config.apply(&budgeted, Some(1), Some(1)), budgeted);
+        assert_eq!(
+            config.apply(&[transfer.clone()], None, None),
+            vec![transfer]
+        );
+    }
+}
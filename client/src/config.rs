@@ -1,15 +1,35 @@
 use dotenv::dotenv;
 use std::env;
 
-pub const WAIT_TIME_AFTER_TRANSACTION: u64 = 15;
+use crate::cluster::{Cluster, ParseClusterError};
 
-pub fn get_cluster_urls() -> (String, String) {
+pub const CONFIRMATION_TIMEOUT_SECS: u64 = 60;
+pub const CONFIRMATION_POLL_INTERVAL_MS: u64 = 500;
+pub const COMPUTE_UNIT_MARGIN_PERCENT: u64 = 10;
+
+// SOLANA_CLUSTER picks the profile (localnet, devnet, mainnet-beta or custom:<url>),
+// devnet when unset
+pub fn get_cluster() -> Result<Cluster, ParseClusterError> {
     dotenv().ok(); // Load environment variables from a .env file
 
-    let cluster_url = env::var("SOLANA_CLUSTER_URL")
-        .unwrap_or_else(|_| "https://api.devnet.solana.com".to_string());
-    let websocket_url = env::var("SOLANA_WEBSOCKET_URL")
-        .unwrap_or_else(|_| "wss://dapi.devnet.solana.com".to_string());
+    non_empty_var("SOLANA_CLUSTER")
+        .map(|cluster| cluster.parse())
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
+// The cluster endpoints unless overridden, e.g. with a private RPC
+pub fn get_cluster_urls(cluster: &Cluster) -> (String, String) {
+    dotenv().ok();
+
+    let cluster_url = non_empty_var("SOLANA_CLUSTER_URL").unwrap_or_else(|| cluster.rpc_url());
+    let websocket_url =
+        non_empty_var("SOLANA_WEBSOCKET_URL").unwrap_or_else(|| cluster.websocket_url());
 
     (cluster_url, websocket_url)
-}
\ No newline at end of file
+}
+
+// `.env.template` leaves the variables blank
+fn non_empty_var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
@@ -0,0 +1,302 @@
+use log::debug;
+use solana_client::pubsub_client::PubsubClient;
+use solana_client::rpc_config::RpcSignatureSubscribeConfig;
+use solana_client::rpc_response::RpcSignatureResult;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::TransactionError;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+use crate::backend::TransactionBackend;
+use crate::config::{CONFIRMATION_POLL_INTERVAL_MS, CONFIRMATION_TIMEOUT_SECS};
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationConfig {
+    pub commitment: CommitmentConfig,
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+    // when set, wait on a signature subscription instead of polling the backend
+    pub websocket_url: Option<String>,
+}
+
+impl Default for ConfirmationConfig {
+    fn default() -> Self {
+        Self {
+            commitment: CommitmentConfig::confirmed(),
+            timeout: Duration::from_secs(CONFIRMATION_TIMEOUT_SECS),
+            poll_interval: Duration::from_millis(CONFIRMATION_POLL_INTERVAL_MS),
+            websocket_url: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfirmationStage {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl From<TransactionConfirmationStatus> for ConfirmationStage {
+    fn from(status: TransactionConfirmationStatus) -> Self {
+        match status {
+            TransactionConfirmationStatus::Processed => Self::Processed,
+            TransactionConfirmationStatus::Confirmed => Self::Confirmed,
+            TransactionConfirmationStatus::Finalized => Self::Finalized,
+        }
+    }
+}
+
+impl From<CommitmentConfig> for ConfirmationStage {
+    fn from(commitment: CommitmentConfig) -> Self {
+        if commitment.is_finalized() {
+            Self::Finalized
+        } else if commitment.is_at_least_confirmed() {
+            Self::Confirmed
+        } else {
+            Self::Processed
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConfirmationReport {
+    pub signature: Signature,
+    pub slot: Option<u64>,
+    // time elapsed since the transaction was sent when each stage was first observed
+    pub stages: Vec<(ConfirmationStage, Duration)>,
+}
+
+impl ConfirmationReport {
+    pub(crate) fn new(signature: Signature) -> Self {
+        Self {
+            signature,
+            slot: None,
+            stages: Vec::new(),
+        }
+    }
+
+    // Records every stage up to `reached` that was not seen yet
+    fn observe(&mut self, reached: ConfirmationStage, elapsed: Duration) {
+        for stage in [
+            ConfirmationStage::Processed,
+            ConfirmationStage::Confirmed,
+            ConfirmationStage::Finalized,
+        ] {
+            if stage <= reached && !self.stages.iter().any(|(seen, _)| *seen == stage) {
+                self.stages.push((stage, elapsed));
+            }
+        }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.stages
+            .last()
+            .map(|(_, elapsed)| *elapsed)
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfirmationError {
+    #[error("Transaction {signature} not {commitment:?} after {elapsed:?}")]
+    Timeout {
+        signature: Signature,
+        commitment: ConfirmationStage,
+        elapsed: Duration,
+    },
+
+    #[error("Transaction {signature} failed: {err}")]
+    TransactionFailed {
+        signature: Signature,
+        err: TransactionError,
+    },
+
+    #[error("Signature subscription failed: {0}")]
+    Subscription(String),
+
+    #[error(transparent)]
+    Backend(#[from] anyhow::Error),
+}
+
+// Waits until `signature` reaches the configured commitment, `sent_at` is when it was submitted
+pub fn confirm_transaction(
+    backend: &dyn TransactionBackend,
+    signature: &Signature,
+    config: &ConfirmationConfig,
+    sent_at: Instant,
+) -> Result<ConfirmationReport, ConfirmationError> {
+    let report = match &config.websocket_url {
+        Some(url) => wait_for_notification(backend, url, signature, config, sent_at)?,
+        None => poll_signature_status(backend, signature, config, sent_at)?,
+    };
+    debug!("\nConfirmation: {:?}\n\n", report);
+    Ok(report)
+}
+
+fn poll_signature_status(
+    backend: &dyn TransactionBackend,
+    signature: &Signature,
+    config: &ConfirmationConfig,
+    sent_at: Instant,
+) -> Result<ConfirmationReport, ConfirmationError> {
+    let mut report = ConfirmationReport::new(*signature);
+    loop {
+        if let Some(status) = backend.get_signature_status(signature)? {
+            if check_status(&mut report, status, config, sent_at)? {
+                return Ok(report);
+            }
+        }
+        if sent_at.elapsed() >= config.timeout {
+            return Err(timeout(signature, config, sent_at));
+        }
+        std::thread::sleep(config.poll_interval);
+    }
+}
+
+fn wait_for_notification(
+    backend: &dyn TransactionBackend,
+    url: &str,
+    signature: &Signature,
+    config: &ConfirmationConfig,
+    sent_at: Instant,
+) -> Result<ConfirmationReport, ConfirmationError> {
+    let (mut subscription, receiver) = PubsubClient::signature_subscribe(
+        url,
+        signature,
+        Some(RpcSignatureSubscribeConfig {
+            commitment: Some(config.commitment),
+            enable_received_notification: Some(false),
+        }),
+    )
+    .map_err(|e| ConfirmationError::Subscription(e.to_string()))?;
+
+    // the transaction may have landed before the subscription was opened
+    let mut report = ConfirmationReport::new(*signature);
+    if let Some(status) = backend.get_signature_status(signature)? {
+        if check_status(&mut report, status, config, sent_at)? {
+            let _ = subscription.shutdown();
+            return Ok(report);
+        }
+    }
+
+    let remaining = config.timeout.saturating_sub(sent_at.elapsed());
+    let notification = receiver.recv_timeout(remaining);
+    let _ = subscription.shutdown();
+    match notification {
+        Ok(response) => match response.value {
+            RpcSignatureResult::ProcessedSignature(result) => {
+                if let Some(err) = result.err {
+                    return Err(ConfirmationError::TransactionFailed {
+                        signature: *signature,
+                        err,
+                    });
+                }
+                report.slot = Some(response.context.slot);
+                report.observe(config.commitment.into(), sent_at.elapsed());
+                Ok(report)
+            }
+            RpcSignatureResult::ReceivedSignature(_) => Err(ConfirmationError::Subscription(
+                "Unexpected received notification".to_string(),
+            )),
+        },
+        Err(_) => Err(timeout(signature, config, sent_at)),
+    }
+}
+
+// Returns true once the status satisfies the configured commitment
+pub(crate) fn check_status(
+    report: &mut ConfirmationReport,
+    status: TransactionStatus,
+    config: &ConfirmationConfig,
+    sent_at: Instant,
+) -> Result<bool, ConfirmationError> {
+    if let Some(err) = status.err.clone() {
+        return Err(ConfirmationError::TransactionFailed {
+            signature: report.signature,
+            err,
+        });
+    }
+    report.slot = Some(status.slot);
+    report.observe(status.confirmation_status().into(), sent_at.elapsed());
+    Ok(status.satisfies_commitment(config.commitment))
+}
+
+pub(crate) fn timeout(
+    signature: &Signature,
+    config: &ConfirmationConfig,
+    sent_at: Instant,
+) -> ConfirmationError {
+    ConfirmationError::Timeout {
+        signature: *signature,
+        commitment: config.commitment.into(),
+        elapsed: sent_at.elapsed(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+    use solana_sdk::hash::Hash;
+    use solana_sdk::signature::{Keypair, Signer};
+    use solana_sdk::system_instruction;
+    use solana_sdk::transaction::Transaction;
+
+    fn quick_config() -> ConfirmationConfig {
+        ConfirmationConfig {
+            timeout: Duration::from_millis(50),
+            poll_interval: Duration::from_millis(10),
+            ..ConfirmationConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_confirm_sent_transaction() {
+        let backend = MockBackend::default();
+        let payer = Keypair::new();
+        let txn = Transaction::new_signed_with_payer(
+            &[system_instruction::transfer(
+                &payer.pubkey(),
+                &Keypair::new().pubkey(),
+                1,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            Hash::new_unique(),
+        );
+        let signature = backend.send_transaction(&txn).unwrap();
+
+        let report =
+            confirm_transaction(&backend, &signature, &quick_config(), Instant::now()).unwrap();
+        let stages: Vec<_> = report.stages.iter().map(|(stage, _)| *stage).collect();
+        assert_eq!(
+            stages,
+            vec![
+                ConfirmationStage::Processed,
+                ConfirmationStage::Confirmed,
+                ConfirmationStage::Finalized
+            ]
+        );
+    }
+
+    #[test]
+    fn test_confirm_unknown_signature_times_out() {
+        let backend = MockBackend::default();
+        let result = confirm_transaction(
+            &backend,
+            &Signature::new_unique(),
+            &quick_config(),
+            Instant::now(),
+        );
+        assert!(matches!(
+            result,
+            Err(ConfirmationError::Timeout {
+                commitment: ConfirmationStage::Confirmed,
+                ..
+            })
+        ));
+    }
+}
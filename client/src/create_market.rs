@@ -0,0 +1,317 @@
+use ansi_term::Colour;
+use log::{debug, info};
+use std::error::Error;
+
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::Keypair;
+use solana_sdk::signer::Signer;
+use solana_sdk::{system_instruction, sysvar};
+use thiserror::Error;
+
+use crate::cache::Market;
+use crate::client_helper::{ClientHelper, WalletKeys};
+use crate::pool_keys::find_vault_signer_and_nonce;
+
+// Account sizes used by the Raydium SDK (marketV2.create), the dex pads every slab with 12 bytes
+pub const MARKET_STATE_SIZE: usize = 388;
+pub const REQUEST_QUEUE_SIZE: usize = 5120 + 12;
+pub const EVENT_QUEUE_SIZE: usize = 262144 + 12;
+pub const ORDERBOOK_SIZE: usize = 65536 + 12;
+
+pub const DEFAULT_QUOTE_DUST_THRESHOLD: u64 = 100;
+
+// MarketInstruction::InitializeMarket, packed as version byte + u32 tag
+const INSTRUCTION_VERSION: u8 = 0;
+const INITIALIZE_MARKET_TAG: u32 = 0;
+
+#[derive(Debug, Error)]
+pub enum CreateMarketError {
+    #[error("Base and quote mints must differ")]
+    SameMint,
+
+    #[error("Lot size {0} rounds to zero base lots")]
+    InvalidLotSize(f64),
+
+    #[error("Tick size {0} rounds to zero quote lots")]
+    InvalidTickSize(f64),
+
+    #[error("Internal error: {0}")]
+    InternalError(#[from] anyhow::Error),
+}
+
+// Market parameters, `lot_size` is in base tokens and `tick_size` in quote tokens per base token
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateMarketConfig {
+    pub market_program: Pubkey,
+    pub base_mint: Pubkey,
+    pub base_decimals: u8,
+    pub quote_mint: Pubkey,
+    pub quote_decimals: u8,
+    pub lot_size: f64,
+    pub tick_size: f64,
+    pub quote_dust_threshold: u64,
+}
+
+impl CreateMarketConfig {
+    // Same conversion as the Raydium SDK
+    pub fn base_lot_size(&self) -> Result<u64, CreateMarketError> {
+        let lots = (self.lot_size * 10f64.powi(self.base_decimals as i32)).round();
+        if !(1.0..=u64::MAX as f64).contains(&lots) {
+            return Err(CreateMarketError::InvalidLotSize(self.lot_size));
+        }
+        Ok(lots as u64)
+    }
+
+    pub fn quote_lot_size(&self) -> Result<u64, CreateMarketError> {
+        let lots =
+            (self.lot_size * self.tick_size * 10f64.powi(self.quote_decimals as i32)).round();
+        if !(1.0..=u64::MAX as f64).contains(&lots) {
+            return Err(CreateMarketError::InvalidTickSize(self.tick_size));
+        }
+        Ok(lots as u64)
+    }
+}
+
+// Instructions to create a market, the vaults go first since the market checks them on init.
+// Every account is created with a seed off the payer so the transactions need no extra signers.
+#[derive(Debug, Clone)]
+pub struct CreateMarketInstructions {
+    pub vaults: Vec<Instruction>,
+    pub market: Vec<Instruction>,
+    pub accounts: Market,
+}
+
+pub fn build_create_market_ix(
+    client: &ClientHelper,
+    config: &CreateMarketConfig,
+) -> Result<CreateMarketInstructions, CreateMarketError> {
+    if config.base_mint == config.quote_mint {
+        return Err(CreateMarketError::SameMint);
+    }
+    let base_lot_size = config.base_lot_size()?;
+    let quote_lot_size = config.quote_lot_size()?;
+    let payer = client.payer();
+    let program = &config.market_program;
+
+    let (market_seed, market_id) = seeded_address(&payer, program)?;
+    let (request_queue_seed, request_queue) = seeded_address(&payer, program)?;
+    let (event_queue_seed, event_queue) = seeded_address(&payer, program)?;
+    let (bids_seed, bids) = seeded_address(&payer, program)?;
+    let (asks_seed, asks) = seeded_address(&payer, program)?;
+    let (base_vault_seed, base_vault) = seeded_address(&payer, &spl_token::id())?;
+    let (quote_vault_seed, quote_vault) = seeded_address(&payer, &spl_token::id())?;
+    let (vault_signer, vault_signer_nonce) = find_vault_signer_and_nonce(&market_id, program)?;
+
+    let mut vaults = Vec::new();
+    for (seed, vault, mint) in [
+        (&base_vault_seed, &base_vault, &config.base_mint),
+        (&quote_vault_seed, &quote_vault, &config.quote_mint),
+    ] {
+        vaults.push(create_account_with_seed(
+            &payer,
+            vault,
+            seed,
+            spl_token::state::Account::LEN,
+            &spl_token::id(),
+        ));
+        vaults.push(
+            spl_token::instruction::initialize_account3(
+                &spl_token::id(),
+                vault,
+                mint,
+                &vault_signer,
+            )
+            .map_err(anyhow::Error::from)?,
+        );
+    }
+
+    let mut market = Vec::new();
+    for (seed, address, space) in [
+        (&request_queue_seed, &request_queue, REQUEST_QUEUE_SIZE),
+        (&event_queue_seed, &event_queue, EVENT_QUEUE_SIZE),
+        (&bids_seed, &bids, ORDERBOOK_SIZE),
+        (&asks_seed, &asks, ORDERBOOK_SIZE),
+        (&market_seed, &market_id, MARKET_STATE_SIZE),
+    ] {
+        market.push(create_account_with_seed(
+            &payer, address, seed, space, program,
+        ));
+    }
+
+    let mut data = vec![INSTRUCTION_VERSION];
+    data.extend_from_slice(&INITIALIZE_MARKET_TAG.to_le_bytes());
+    data.extend_from_slice(&base_lot_size.to_le_bytes());
+    data.extend_from_slice(&quote_lot_size.to_le_bytes());
+    data.extend_from_slice(&0u16.to_le_bytes()); // fee_rate_bps, unused by the dex
+    data.extend_from_slice(&vault_signer_nonce.to_le_bytes());
+    data.extend_from_slice(&config.quote_dust_threshold.to_le_bytes());
+    market.push(Instruction {
+        program_id: *program,
+        accounts: vec![
+            AccountMeta::new(market_id, false),
+            AccountMeta::new(request_queue, false),
+            AccountMeta::new(event_queue, false),
+            AccountMeta::new(bids, false),
+            AccountMeta::new(asks, false),
+            AccountMeta::new(base_vault, false),
+            AccountMeta::new(quote_vault, false),
+            AccountMeta::new_readonly(config.base_mint, false),
+            AccountMeta::new_readonly(config.quote_mint, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ],
+        data,
+    });
+
+    let accounts = Market {
+        market_id: market_id.to_string(),
+        request_queue: request_queue.to_string(),
+        event_queue: event_queue.to_string(),
+        bids: bids.to_string(),
+        asks: asks.to_string(),
+        base_vault: base_vault.to_string(),
+        quote_vault: quote_vault.to_string(),
+        base_mint: config.base_mint.to_string(),
+        quote_mint: config.quote_mint.to_string(),
+    };
+    debug!(
+        "Market {} with base lot {} and quote lot {}",
+        market_id, base_lot_size, quote_lot_size
+    );
+    Ok(CreateMarketInstructions {
+        vaults,
+        market,
+        accounts,
+    })
+}
+
+// Function to create a market and save it to the cache
+pub fn create_market(
+    client: &ClientHelper,
+    config: &CreateMarketConfig,
+) -> Result<Market, Box<dyn Error>> {
+    let instructions = build_create_market_ix(client, config)?;
+    info!("Creating market {}", instructions.accounts.market_id);
+    client.process_transaction(&instructions.vaults, false)?;
+    client.process_transaction(&instructions.market, false)?;
+    instructions
+        .accounts
+        .write_to_dir(&client.cache_dir, &client.cluster)?;
+    info!("{}", Colour::Green.paint("Market successfully created"));
+    Ok(instructions.accounts)
+}
+
+// Same 32 chars seed scheme as the Raydium SDK
+fn seeded_address(base: &Pubkey, owner: &Pubkey) -> anyhow::Result<(String, Pubkey)> {
+    let seed = Keypair::new().pubkey().to_string()[..32].to_string();
+    let address = Pubkey::create_with_seed(base, &seed, owner)?;
+    Ok((seed, address))
+}
+
+fn create_account_with_seed(
+    payer: &Pubkey,
+    address: &Pubkey,
+    seed: &str,
+    space: usize,
+    owner: &Pubkey,
+) -> Instruction {
+    system_instruction::create_account_with_seed(
+        payer,
+        address,
+        payer,
+        seed,
+        Rent::default().minimum_balance(space),
+        space as u64,
+        owner,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program_test::Harness;
+    use std::str::FromStr;
+
+    #[test]
+    fn test_lot_sizes_match_sdk() {
+        let config = CreateMarketConfig {
+            market_program: Pubkey::new_unique(),
+            base_mint: Pubkey::new_unique(),
+            base_decimals: 6,
+            quote_mint: Pubkey::new_unique(),
+            quote_decimals: 6,
+            lot_size: 1.0,
+            tick_size: 0.01,
+            quote_dust_threshold: DEFAULT_QUOTE_DUST_THRESHOLD,
+        };
+        assert_eq!(config.base_lot_size().unwrap(), 1_000_000);
+        assert_eq!(config.quote_lot_size().unwrap(), 10_000);
+
+        let too_fine = CreateMarketConfig {
+            tick_size: 0.000_000_1,
+            ..config
+        };
+        assert!(matches!(
+            too_fine.quote_lot_size(),
+            Err(CreateMarketError::InvalidTickSize(_))
+        ));
+    }
+
+    #[test]
+    fn test_offline_build_create_market_ix() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+        let config = CreateMarketConfig {
+            market_program: pool_keys.market_program,
+            base_mint: pool_keys.coin_mint,
+            base_decimals: 6,
+            quote_mint: pool_keys.pc_mint,
+            quote_decimals: 6,
+            lot_size: 1.0,
+            tick_size: 0.01,
+            quote_dust_threshold: DEFAULT_QUOTE_DUST_THRESHOLD,
+        };
+
+        // EXECUTE
+        let instructions = build_create_market_ix(&client, &config).unwrap();
+        for batch in [&instructions.vaults, &instructions.market] {
+            let result = client.process_transaction(batch, false);
+            assert!(
+                result.is_ok(),
+                "create market transaction failed with error: {:?}",
+                result.unwrap_err()
+            );
+        }
+
+        // EXPECT
+        for (address, size) in [
+            (&instructions.accounts.market_id, MARKET_STATE_SIZE),
+            (&instructions.accounts.request_queue, REQUEST_QUEUE_SIZE),
+            (&instructions.accounts.event_queue, EVENT_QUEUE_SIZE),
+            (&instructions.accounts.bids, ORDERBOOK_SIZE),
+            (&instructions.accounts.asks, ORDERBOOK_SIZE),
+        ] {
+            let account = client
+                .get_account(&Pubkey::from_str(address).unwrap())
+                .unwrap();
+            assert_eq!(account.owner, pool_keys.market_program);
+            assert_eq!(account.data.len(), size);
+        }
+        let market_id = Pubkey::from_str(&instructions.accounts.market_id).unwrap();
+        let vault_signer =
+            crate::pool_keys::find_vault_signer(&market_id, &pool_keys.market_program).unwrap();
+        let base_vault = client
+            .get_account(&Pubkey::from_str(&instructions.accounts.base_vault).unwrap())
+            .unwrap();
+        let base_vault = spl_token::state::Account::unpack(&base_vault.data).unwrap();
+        assert_eq!(
+            base_vault.owner, vault_signer,
+            "Vaults should belong to the vault signer"
+        );
+        assert_eq!(base_vault.mint, pool_keys.coin_mint);
+    }
+}
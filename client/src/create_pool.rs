@@ -0,0 +1,260 @@
+use ansi_term::Colour;
+use log::{debug, info};
+use std::error::Error;
+
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use spl_associated_token_account::get_associated_token_address;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::cache::Pool;
+use crate::client_helper::{ClientHelper, WalletKeys};
+use crate::fetch_pool::decode_market;
+use crate::math::initial_liquidity;
+use crate::pool_keys::{derive_pool, find_amm_authority, find_create_pool_fee_destination};
+use crate::token::UserTokenAccount;
+
+#[derive(Debug, Error)]
+pub enum CreatePoolError {
+    #[error("Amount must be greater than zero")]
+    AmountZero,
+
+    #[error("{0} is not an AMM v4 program")]
+    UnknownAmmProgram(Pubkey),
+
+    #[error("Market {0} is not owned by the market program")]
+    InvalidMarket(Pubkey),
+
+    // AMM v4 pools sit on an OpenBook market, token-2022 mints need a CPMM pool instead
+    #[error("Mint {0} is not owned by the SPL token program")]
+    UnsupportedMint(Pubkey),
+
+    // The program locks 10^decimals LP forever, the initial liquidity has to exceed it
+    #[error("Initial liquidity too low, add more coin or pc")]
+    InitialLiquidityTooLow,
+
+    #[error("Internal error: {0}")]
+    InternalError(#[from] anyhow::Error),
+}
+
+// Pool parameters, amounts are raw (with decimals)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatePoolConfig {
+    pub amm_program: Pubkey,
+    pub market_program: Pubkey,
+    pub market_id: Pubkey,
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    // unix timestamp before which swaps are rejected, 0 opens the pool right away
+    pub open_time: u64,
+}
+
+// initialize2 mints sqrt(coin * pc) LP and locks 10^lp_decimals of it, the LP mint takes
+// the coin decimals
+fn check_initial_liquidity(
+    coin_amount: u64,
+    pc_amount: u64,
+    lp_decimals: u8,
+) -> Result<(), CreatePoolError> {
+    let liquidity = initial_liquidity(coin_amount, pc_amount);
+    let locked_lp = 10u64.checked_pow(lp_decimals as u32);
+    match (liquidity, locked_lp) {
+        (Some(liquidity), Some(locked_lp)) if liquidity > locked_lp => Ok(()),
+        _ => Err(CreatePoolError::InitialLiquidityTooLow),
+    }
+}
+
+pub fn build_create_pool_ix(
+    client: &ClientHelper,
+    config: &CreatePoolConfig,
+) -> Result<(Vec<Instruction>, Pool), CreatePoolError> {
+    if config.coin_amount == 0 || config.pc_amount == 0 {
+        return Err(CreatePoolError::AmountZero);
+    }
+    let fee_destination = find_create_pool_fee_destination(&config.amm_program)
+        .ok_or(CreatePoolError::UnknownAmmProgram(config.amm_program))?;
+
+    // The pool mints are the market ones
+    let market = client.get_account(&config.market_id)?;
+    if market.owner != config.market_program {
+        return Err(CreatePoolError::InvalidMarket(config.market_id));
+    }
+    let market = decode_market(&config.market_id, &market.data)?;
+    let coin_mint = Pubkey::from_str(&market.base_mint).map_err(anyhow::Error::from)?;
+    let pc_mint = Pubkey::from_str(&market.quote_mint).map_err(anyhow::Error::from)?;
+
+    let mut coin_decimals = 0;
+    for mint in [coin_mint, pc_mint] {
+        let account = client.get_account(&mint)?;
+        if account.owner != spl_token::id() {
+            return Err(CreatePoolError::UnsupportedMint(mint));
+        }
+        if mint == coin_mint {
+            let state =
+                spl_token::state::Mint::unpack(&account.data).map_err(anyhow::Error::from)?;
+            coin_decimals = state.decimals;
+        }
+    }
+
+    check_initial_liquidity(config.coin_amount, config.pc_amount, coin_decimals)?;
+
+    let pool = derive_pool(
+        &config.amm_program,
+        &config.market_program,
+        &config.market_id,
+        &coin_mint,
+        &pc_mint,
+    );
+    let (amm_authority, nonce) = find_amm_authority(&config.amm_program);
+    let parse = |address: &str| Pubkey::from_str(address).map_err(anyhow::Error::from);
+
+    // wrapped SOL sides are funded from lamports, the program creates the LP account itself.
+    // initialize2 charges the creation fee and the pool rent to the owner, not to a sponsor
+    let owner = client.owner();
+    let payer = client.payer();
+    let coin = UserTokenAccount::source(&payer, &owner, &coin_mint, config.coin_amount);
+    let pc = UserTokenAccount::source(&payer, &owner, &pc_mint, config.pc_amount);
+    let lp_mint = parse(&pool.lp_mint)?;
+    let initialize_ix = raydium_amm::instruction::initialize2(
+        &config.amm_program,
+        &parse(&pool.amm_id)?,
+        &amm_authority,
+        &parse(&pool.amm_open_orders)?,
+        &lp_mint,
+        &coin_mint,
+        &pc_mint,
+        &parse(&pool.coin_vault)?,
+        &parse(&pool.pc_vault)?,
+        &parse(&pool.amm_target_orders)?,
+        &parse(&pool.amm_config_id)?,
+        &fee_destination,
+        &config.market_program,
+        &config.market_id,
+        &owner,
+        &coin.address,
+        &pc.address,
+        &get_associated_token_address(&owner, &lp_mint),
+        nonce,
+        config.open_time,
+        config.pc_amount,
+        config.coin_amount,
+    )
+    .map_err(anyhow::Error::from)?;
+    debug!("Pool to create: {:?}", pool);
+
+    let mut instructions = [coin.setup, pc.setup].concat();
+    instructions.push(initialize_ix);
+    instructions.extend([coin.cleanup, pc.cleanup].concat());
+    Ok((instructions, pool))
+}
+
+// Function to create a pool and save it to the cache
+pub fn create_pool(
+    client: &ClientHelper,
+    config: &CreatePoolConfig,
+) -> Result<Pool, Box<dyn Error>> {
+    let (instructions, pool) = build_create_pool_ix(client, config)?;
+    info!(
+        "Creating pool {} with {} coin and {} pc",
+        pool.amm_id, config.coin_amount, config.pc_amount
+    );
+    client.process_transaction(&instructions, false)?;
+    pool.write_to_dir(&client.cache_dir, &client.cluster)?;
+    info!("{}", Colour::Green.paint("Pool successfully created"));
+    Ok(pool)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_market::{
+        build_create_market_ix, CreateMarketConfig, DEFAULT_QUOTE_DUST_THRESHOLD,
+    };
+    use crate::program_test::Harness;
+
+    #[test]
+    fn test_check_initial_liquidity_boundary() {
+        // sqrt(1_000_000 * 1_000_000) is exactly the 10^6 locked LP
+        assert!(matches!(
+            check_initial_liquidity(1_000_000, 1_000_000, 6),
+            Err(CreatePoolError::InitialLiquidityTooLow)
+        ));
+        // 1_000_001^2 > 1_000_000 * 1_000_002, the root still floors to 10^6
+        assert!(matches!(
+            check_initial_liquidity(1_000_000, 1_000_002, 6),
+            Err(CreatePoolError::InitialLiquidityTooLow)
+        ));
+        assert!(check_initial_liquidity(1_000_001, 1_000_001, 6).is_ok());
+        // 10^20 does not fit the program's u64
+        assert!(check_initial_liquidity(u64::MAX, u64::MAX, 20).is_err());
+    }
+
+    #[test]
+    fn test_offline_build_create_pool_ix() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+        let market = build_create_market_ix(
+            &client,
+            &CreateMarketConfig {
+                market_program: pool_keys.market_program,
+                base_mint: pool_keys.coin_mint,
+                base_decimals: 6,
+                quote_mint: pool_keys.pc_mint,
+                quote_decimals: 6,
+                lot_size: 1.0,
+                tick_size: 0.01,
+                quote_dust_threshold: DEFAULT_QUOTE_DUST_THRESHOLD,
+            },
+        )
+        .unwrap();
+        client.process_transaction(&market.vaults, false).unwrap();
+        client.process_transaction(&market.market, false).unwrap();
+        let market_id = Pubkey::from_str(&market.accounts.market_id).unwrap();
+
+        // BEFORE
+        let pre_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+
+        // EXECUTE
+        let mut config = CreatePoolConfig {
+            amm_program: pool_keys.amm_program,
+            market_program: pool_keys.market_program,
+            market_id,
+            coin_amount: 1_000_000,
+            pc_amount: 1_000_000,
+            open_time: 0,
+        };
+        let too_low = build_create_pool_ix(&client, &config);
+        assert!(matches!(
+            too_low,
+            Err(CreatePoolError::InitialLiquidityTooLow)
+        ));
+
+        config.coin_amount = 100_000_000;
+        config.pc_amount = 100_000_000;
+        let (instructions, pool) = build_create_pool_ix(&client, &config).unwrap();
+        let result = client.process_transaction(&instructions, false);
+        assert!(
+            result.is_ok(),
+            "create pool transaction failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+        assert_eq!(
+            pre_user_coin_amount - after_user_coin_amount,
+            config.coin_amount
+        );
+        let amm_info = client
+            .fetch_amm_info(&Pubkey::from_str(&pool.amm_id).unwrap())
+            .unwrap();
+        assert_eq!(amm_info.market.to_string(), pool.market_id);
+        assert_eq!(amm_info.lp_mint.to_string(), pool.lp_mint);
+        assert_eq!(amm_info.coin_vault.to_string(), pool.coin_vault);
+        assert_eq!(amm_info.target_orders.to_string(), pool.amm_target_orders);
+    }
+}
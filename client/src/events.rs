@@ -0,0 +1,131 @@
+use anchor_lang::{AnchorDeserialize, Discriminator};
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use log::{debug, warn};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+
+pub use raydium_puppet::events::{DepositEvent, SwapEvent, WithdrawEvent};
+
+use crate::client_helper::ClientHelper;
+
+const PROGRAM_DATA: &str = "Program data: ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuppetEvent {
+    Deposit(DepositEvent),
+    Withdraw(WithdrawEvent),
+    Swap(SwapEvent),
+}
+
+impl PuppetEvent {
+    // Anchor event payload: 8 bytes discriminator followed by the borsh encoded struct
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        let (discriminator, mut payload) = data.split_at(8);
+        let event = if discriminator == DepositEvent::DISCRIMINATOR {
+            PuppetEvent::Deposit(DepositEvent::deserialize(&mut payload).ok()?)
+        } else if discriminator == WithdrawEvent::DISCRIMINATOR {
+            PuppetEvent::Withdraw(WithdrawEvent::deserialize(&mut payload).ok()?)
+        } else if discriminator == SwapEvent::DISCRIMINATOR {
+            PuppetEvent::Swap(SwapEvent::deserialize(&mut payload).ok()?)
+        } else {
+            return None;
+        };
+        Some(event)
+    }
+}
+
+// Walks the invoke/success stack so only `Program data:` lines logged by the puppet itself are decoded,
+// the AMM and token programs it calls into log their own data in between
+pub fn parse_events(logs: &[String]) -> Vec<PuppetEvent> {
+    parse_events_for_program(logs, &raydium_puppet::ID)
+}
+
+pub fn parse_events_for_program(logs: &[String], program_id: &Pubkey) -> Vec<PuppetEvent> {
+    let program_id = program_id.to_string();
+    let mut stack: Vec<&str> = Vec::new();
+    let mut events = Vec::new();
+    for log in logs {
+        let mut words = log.split_whitespace();
+        match (words.next(), words.next(), words.next()) {
+            (Some("Program"), Some(id), Some("invoke")) => stack.push(id),
+            (Some("Program"), Some(id), Some("success"))
+            | (Some("Program"), Some(id), Some("failed:")) => {
+                if stack.last() == Some(&id) {
+                    stack.pop();
+                }
+            }
+            _ => {
+                let Some(data) = log.strip_prefix(PROGRAM_DATA) else {
+                    continue;
+                };
+                if stack.last() != Some(&program_id.as_str()) {
+                    continue;
+                }
+                match STANDARD.decode(data) {
+                    Ok(bytes) => match PuppetEvent::decode(&bytes) {
+                        Some(event) => events.push(event),
+                        None => debug!("Skipping unknown program data: {}", data),
+                    },
+                    Err(e) => warn!("Invalid base64 in program data: {:?}", e),
+                }
+            }
+        }
+    }
+    events
+}
+
+impl ClientHelper {
+    pub fn fetch_events(&self, signature: &Signature) -> Result<Vec<PuppetEvent>> {
+        let logs = self.backend.get_transaction_logs(signature)?;
+        Ok(parse_events(&logs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::Event;
+
+    fn deposit_event() -> DepositEvent {
+        DepositEvent {
+            amm: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            lp_minted: 1_000_000,
+            coin_amount: 1_000_000,
+            pc_amount: 1_000_001,
+            coin_vault_balance: 42_000_000,
+            pc_vault_balance: 42_000_042,
+        }
+    }
+
+    #[test]
+    fn test_parse_events_only_from_puppet() {
+        let puppet = raydium_puppet::ID.to_string();
+        let amm = Pubkey::new_unique().to_string();
+        let event = deposit_event();
+        let data = format!("{}{}", PROGRAM_DATA, STANDARD.encode(event.data()));
+        let logs: Vec<String> = vec![
+            format!("Program {} invoke [1]", puppet),
+            format!("Program {} invoke [2]", amm),
+            // same payload logged by the AMM must be ignored
+            data.clone(),
+            format!("Program {} success", amm),
+            data,
+            format!("Program {} success", puppet),
+        ];
+
+        let events = parse_events(&logs);
+        assert_eq!(events, vec![PuppetEvent::Deposit(event)]);
+    }
+
+    #[test]
+    fn test_decode_unknown_discriminator() {
+        assert_eq!(PuppetEvent::decode(&[0u8; 4]), None);
+        assert_eq!(PuppetEvent::decode(&[0u8; 64]), None);
+    }
+}
@@ -4,22 +4,27 @@ use std::error::Error;
 use std::str::FromStr;
 
 use raydium_library::common;
+use raydium_puppet::constants::AMM_ASSOCIATED_SEED;
 use solana_sdk::pubkey::Pubkey;
 
-use crate::cache::Pool;
+use crate::cache::{Market, Pool};
 use crate::client_helper::ClientHelper;
+use crate::pool_keys::{derive_pool, find_amm_associated_address};
 
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::account::Account;
 use solana_sdk::program_pack::Pack;
 use spl_token::state::Account as TokenAccount;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ExtendedAmmInfo {
     pub amm_info: raydium_amm::state::AmmInfo,
     pub lp_amount: u64,
     pub coin_vault_balance: u64,
     pub pc_vault_balance: u64,
+    // funds the pool has resting on the OpenBook market, they count towards its reserves
+    pub open_orders_coin_total: u64,
+    pub open_orders_pc_total: u64,
 }
 
 impl ExtendedAmmInfo {
@@ -35,14 +40,148 @@ impl ExtendedAmmInfo {
             lp_amount,
             coin_vault_balance,
             pc_vault_balance,
+            open_orders_coin_total: 0,
+            open_orders_pc_total: 0,
         }
     }
+
+    pub fn with_open_orders(mut self, coin_total: u64, pc_total: u64) -> Self {
+        self.open_orders_coin_total = coin_total;
+        self.open_orders_pc_total = pc_total;
+        self
+    }
+
+    // Vault and open orders balances minus the PnL the pool still owes,
+    // this is what the program prices deposits and withdrawals with
+    pub fn total_without_take_pnl(&self) -> (u64, u64) {
+        (
+            self.coin_vault_balance
+                .saturating_add(self.open_orders_coin_total)
+                .saturating_sub(self.amm_info.state_data.need_take_pnl_coin),
+            self.pc_vault_balance
+                .saturating_add(self.open_orders_pc_total)
+                .saturating_sub(self.amm_info.state_data.need_take_pnl_pc),
+        )
+    }
+}
+
+// AmmInfo is a Pod struct laid out at the start of the account data
+pub fn decode_amm_info(data: &[u8]) -> Result<raydium_amm::state::AmmInfo> {
+    let size = std::mem::size_of::<raydium_amm::state::AmmInfo>();
+    let bytes = data
+        .get(..size)
+        .ok_or_else(|| anyhow::anyhow!("Account data too small for AmmInfo"))?;
+    bytemuck::try_pod_read_unaligned(bytes)
+        .map_err(|e| anyhow::anyhow!("Invalid AmmInfo data: {}", e))
+}
+
+// OpenOrders layout: "serum" padding (5), account_flags (8), market (32), owner (32),
+// native_coin_free (8), native_coin_total (8), native_pc_free (8), native_pc_total (8)
+const OPEN_ORDERS_COIN_TOTAL_OFFSET: usize = 85;
+const OPEN_ORDERS_PC_TOTAL_OFFSET: usize = 101;
+
+pub fn decode_open_orders_totals(data: &[u8]) -> Result<(u64, u64)> {
+    let read_u64 = |offset: usize| -> Result<u64> {
+        let bytes = data
+            .get(offset..offset + 8)
+            .ok_or_else(|| anyhow::anyhow!("Account data too small for OpenOrders"))?;
+        Ok(u64::from_le_bytes(bytes.try_into()?))
+    };
+    Ok((
+        read_u64(OPEN_ORDERS_COIN_TOTAL_OFFSET)?,
+        read_u64(OPEN_ORDERS_PC_TOTAL_OFFSET)?,
+    ))
+}
+
+// MarketState layout: "serum" padding (5), account_flags (8), own_address (32), vault_signer_nonce (8),
+// coin_mint (32), pc_mint (32), coin_vault (32), coin_deposits_total (8), coin_fees_accrued (8),
+// pc_vault (32), pc_deposits_total (8), pc_fees_accrued (8), pc_dust_threshold (8),
+// req_q (32), event_q (32), bids (32), asks (32)
+const MARKET_COIN_MINT_OFFSET: usize = 53;
+const MARKET_PC_MINT_OFFSET: usize = 85;
+const MARKET_COIN_VAULT_OFFSET: usize = 117;
+const MARKET_PC_VAULT_OFFSET: usize = 165;
+const MARKET_REQUEST_QUEUE_OFFSET: usize = 221;
+const MARKET_EVENT_QUEUE_OFFSET: usize = 253;
+const MARKET_BIDS_OFFSET: usize = 285;
+const MARKET_ASKS_OFFSET: usize = 317;
+
+pub fn decode_market(market_id: &Pubkey, data: &[u8]) -> Result<Market> {
+    let read_address = |offset: usize| -> Result<String> {
+        let bytes = data
+            .get(offset..offset + 32)
+            .ok_or_else(|| anyhow::anyhow!("Account data too small for MarketState"))?;
+        Ok(Pubkey::try_from(bytes)?.to_string())
+    };
+    Ok(Market {
+        market_id: market_id.to_string(),
+        request_queue: read_address(MARKET_REQUEST_QUEUE_OFFSET)?,
+        event_queue: read_address(MARKET_EVENT_QUEUE_OFFSET)?,
+        bids: read_address(MARKET_BIDS_OFFSET)?,
+        asks: read_address(MARKET_ASKS_OFFSET)?,
+        base_vault: read_address(MARKET_COIN_VAULT_OFFSET)?,
+        quote_vault: read_address(MARKET_PC_VAULT_OFFSET)?,
+        base_mint: read_address(MARKET_COIN_MINT_OFFSET)?,
+        quote_mint: read_address(MARKET_PC_MINT_OFFSET)?,
+    })
 }
 
 impl ClientHelper {
+    pub fn fetch_market(&self, market_id: &Pubkey) -> Result<Market> {
+        let account = self.get_account(market_id)?;
+        decode_market(market_id, &account.data)
+    }
+
+    // Derives the pool of `market_id` and checks it against the on-chain AmmInfo,
+    // an alternative to Pool::read_from_cache
+    pub fn fetch_pool(&self, amm_program: &Pubkey, market_id: &Pubkey) -> Result<Pool> {
+        let amm_id = find_amm_associated_address(amm_program, market_id, AMM_ASSOCIATED_SEED);
+        let account = self
+            .backend
+            .get_account(&amm_id)?
+            .ok_or_else(|| anyhow::anyhow!("No pool found for market {}", market_id))?;
+        if account.owner != *amm_program {
+            anyhow::bail!("Pool {} is not owned by {}", amm_id, amm_program);
+        }
+        let amm_info = decode_amm_info(&account.data)?;
+        let pool = derive_pool(
+            amm_program,
+            &amm_info.market_program,
+            market_id,
+            &amm_info.coin_vault_mint,
+            &amm_info.pc_vault_mint,
+        );
+        for (field, derived, on_chain) in [
+            ("market", &pool.market_id, amm_info.market),
+            ("open orders", &pool.amm_open_orders, amm_info.open_orders),
+            (
+                "target orders",
+                &pool.amm_target_orders,
+                amm_info.target_orders,
+            ),
+            ("lp mint", &pool.lp_mint, amm_info.lp_mint),
+            ("coin vault", &pool.coin_vault, amm_info.coin_vault),
+            ("pc vault", &pool.pc_vault, amm_info.pc_vault),
+        ] {
+            if *derived != on_chain.to_string() {
+                anyhow::bail!(
+                    "Derived {} {} does not match on-chain {} for pool {}",
+                    field,
+                    derived,
+                    on_chain,
+                    amm_id
+                );
+            }
+        }
+        Ok(pool)
+    }
+
     pub fn fetch_amm_info(&self, pool_id: &Pubkey) -> Result<raydium_amm::state::AmmInfo> {
-        common::rpc::get_account::<raydium_amm::state::AmmInfo>(&self.client, pool_id)?
-            .ok_or_else(|| anyhow::anyhow!("Pool state not found"))
+        let account = self
+            .backend
+            .get_account(pool_id)?
+            .ok_or_else(|| anyhow::anyhow!("Pool state not found"))?;
+        decode_amm_info(&account.data)
     }
 
     pub fn fetch_extended_amm_info(&self, pool_id: &Pubkey) -> Result<ExtendedAmmInfo> {
@@ -58,8 +197,17 @@ impl ClientHelper {
         let lp_balance = amm_info.lp_amount;
         let pc_vault_balance = self.fetch_token_balance(&pc_vault_address)?;
 
+        // Query the funds resting on the market
+        let open_orders = self.get_account(&amm_info.open_orders)?;
+        let (open_orders_coin_total, open_orders_pc_total) =
+            decode_open_orders_totals(&open_orders.data)?;
+
         debug!("Coin Vault Balance: {:?}", coin_vault_balance);
         debug!("PC Vault Balance: {:?}", pc_vault_balance);
+        debug!(
+            "Open Orders Totals: {:?} / {:?}",
+            open_orders_coin_total, open_orders_pc_total
+        );
         debug!("LP Amount: {:?}", lp_balance);
 
         Ok(ExtendedAmmInfo::new(
@@ -67,7 +215,8 @@ impl ClientHelper {
             amm_info.lp_amount,
             coin_vault_balance,
             pc_vault_balance,
-        ))
+        )
+        .with_open_orders(open_orders_coin_total, open_orders_pc_total))
     }
 }
 
@@ -76,6 +225,8 @@ mod tests {
     use crate::cache::Market;
 
     use super::*;
+    use crate::pool_keys::PoolKeys;
+    use crate::program_test::Harness;
     use ctor::ctor;
 
     #[ctor]
@@ -83,6 +234,15 @@ mod tests {
         env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
     }
 
+    #[test]
+    fn test_decode_amm_info_checks_length() {
+        let mut amm_info = raydium_amm::state::AmmInfo::default();
+        amm_info.lp_amount = 42;
+        let data = bytemuck::bytes_of(&amm_info).to_vec();
+        assert_eq!(decode_amm_info(&data).unwrap().lp_amount, 42);
+        assert!(decode_amm_info(&data[..data.len() - 1]).is_err());
+    }
+
     #[test]
     fn test_fetch_amm_info() {
         let client = ClientHelper::default();
@@ -123,4 +283,40 @@ mod tests {
             "PC vault balance should be greater than 0"
         );
     }
+
+    #[test]
+    fn test_offline_fetch_pool_without_cache() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+
+        // EXECUTE
+        let fetched_market = client.fetch_market(&harness.pool_keys.market_id).unwrap();
+        let fetched_pool = client
+            .fetch_pool(&harness.pool_keys.amm_program, &harness.pool_keys.market_id)
+            .unwrap();
+
+        // EXPECT
+        assert_eq!(
+            fetched_market, harness.market,
+            "Decoded market should match the created one"
+        );
+        assert_eq!(
+            fetched_pool, harness.pool,
+            "Derived pool should match the created one"
+        );
+        assert_eq!(
+            PoolKeys::from_cache(&fetched_pool, &fetched_market).unwrap(),
+            harness.pool_keys
+        );
+    }
+
+    #[test]
+    fn test_offline_fetch_pool_unknown_market() {
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+
+        let r = client.fetch_pool(&harness.pool_keys.amm_program, &Pubkey::new_unique());
+        assert!(r.is_err(), "No pool should exist for a random market");
+    }
 }
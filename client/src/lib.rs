@@ -2,12 +2,31 @@
 #![allow(dead_code)]
 pub mod add_liquidity;
 pub mod add_remove_liquidity;
+pub mod async_client;
+pub mod backend;
 pub mod cache;
+pub mod client_builder;
 pub mod client_helper;
+pub mod cluster;
+pub mod compute_budget;
 pub mod config;
+pub mod confirmation;
+pub mod create_market;
+pub mod create_pool;
+pub mod events;
 pub mod fetch_pool;
+pub mod math;
+pub mod pool_keys;
+pub mod program_error;
+#[cfg(test)]
+pub mod program_test;
+pub mod quote;
 pub mod remove_liquidity;
+pub mod swap;
+pub mod token;
+mod transaction_builder;
 
+use solana_sdk::pubkey::Pubkey;
 use std::fmt;
 use thiserror::Error;
 
@@ -30,4 +49,7 @@ pub enum LiquidityError {
 
     #[error("No instructions generated")]
     NoInstructions,
+
+    #[error("Mint {0} is neither the coin nor the pc mint of the pool")]
+    MintNotInPool(Pubkey),
 }
@@ -0,0 +1,198 @@
+// Integer helpers shared by the liquidity builders, all intermediate products are u128
+// and every function returns None instead of overflowing or dividing by zero.
+// Rounding follows the AMM v4 program (`raydium_amm::math`) so quotes match what lands on-chain.
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+// Fractional slippage (0.01 = 1%) to basis points
+pub fn slippage_to_bps(slippage: f64) -> u64 {
+    (slippage * BPS_DENOMINATOR as f64).round() as u64
+}
+
+// The program's CheckedCeilDiv: a quotient below one rounds to the nearest instead of up
+pub fn ceil_div(dividend: u128, divisor: u128) -> Option<u128> {
+    let quotient = dividend.checked_div(divisor)?;
+    if quotient == 0 {
+        return Some(if dividend.checked_mul(2)? >= divisor {
+            1
+        } else {
+            0
+        });
+    }
+    if dividend % divisor > 0 {
+        return quotient.checked_add(1);
+    }
+    Some(quotient)
+}
+
+pub fn mul_div_floor(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+    if denominator == 0 {
+        return None;
+    }
+    let product = (amount as u128).checked_mul(numerator as u128)?;
+    u64::try_from(product / denominator as u128).ok()
+}
+
+pub fn mul_div_ceil(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+    let product = (amount as u128).checked_mul(numerator as u128)?;
+    u64::try_from(ceil_div(product, denominator as u128)?).ok()
+}
+
+pub fn add_slippage_bps(amount: u64, slippage_bps: u64) -> Option<u64> {
+    let factor = (BPS_DENOMINATOR as u128).checked_add(slippage_bps as u128)?;
+    let amount = (amount as u128).checked_mul(factor)? / BPS_DENOMINATOR as u128;
+    u64::try_from(amount).ok()
+}
+
+pub fn sub_slippage_bps(amount: u64, slippage_bps: u64) -> Option<u64> {
+    let factor = (BPS_DENOMINATOR as u128).checked_sub(slippage_bps as u128)?;
+    let amount = (amount as u128).checked_mul(factor)? / BPS_DENOMINATOR as u128;
+    u64::try_from(amount).ok()
+}
+
+// Swap fee taken from the input before it reaches the curve
+pub fn swap_fee(amount_in: u64, fee_numerator: u64, fee_denominator: u64) -> Option<u64> {
+    mul_div_ceil(amount_in, fee_numerator, fee_denominator)
+}
+
+// Constant product output for an input already net of fees, rounded down
+pub fn swap_base_in_amount_out(amount_in: u64, total_in: u64, total_out: u64) -> Option<u64> {
+    let denominator = (total_in as u128).checked_add(amount_in as u128)?;
+    let amount_out = (total_out as u128).checked_mul(amount_in as u128)? / denominator;
+    u64::try_from(amount_out).ok()
+}
+
+// Constant product input, before fees, needed to receive exactly `amount_out`, rounded up
+pub fn swap_base_out_amount_in(amount_out: u64, total_in: u64, total_out: u64) -> Option<u64> {
+    let denominator = total_out.checked_sub(amount_out)?;
+    mul_div_ceil(total_in, amount_out, denominator)
+}
+
+// Grosses up a curve input so that it is still `amount_in` once the swap fee is taken
+pub fn add_swap_fee(amount_in: u64, fee_numerator: u64, fee_denominator: u64) -> Option<u64> {
+    mul_div_ceil(
+        amount_in,
+        fee_denominator,
+        fee_denominator.checked_sub(fee_numerator)?,
+    )
+}
+
+// Floor square root, the program's U128::integer_sqrt
+pub fn integer_sqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    // start above the root, Newton's steps then decrease until they reach the floor
+    let mut root = 1u128 << ((128 - value.leading_zeros() + 1) / 2);
+    loop {
+        let next = (root + value / root) / 2;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+// LP supply initialize2 mints for the first deposit, sqrt(coin * pc)
+pub fn initial_liquidity(coin_amount: u64, pc_amount: u64) -> Option<u64> {
+    let product = (coin_amount as u128).checked_mul(pc_amount as u128)?;
+    u64::try_from(integer_sqrt(product)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use raydium_amm::math::{Calculator, CheckedCeilDiv, SwapDirection, U128};
+
+    #[test]
+    fn test_slippage_helpers() {
+        assert_eq!(slippage_to_bps(0.01), 100);
+        assert_eq!(slippage_to_bps(0.0), 0);
+        assert_eq!(add_slippage_bps(1_000, 100), Some(1_010));
+        assert_eq!(sub_slippage_bps(1_000, 100), Some(990));
+        assert_eq!(sub_slippage_bps(1_000, 10_001), None);
+        assert_eq!(mul_div_floor(10, 1, 3), Some(3));
+        assert_eq!(mul_div_ceil(10, 1, 3), Some(4));
+        assert_eq!(mul_div_ceil(10, 1, 0), None);
+    }
+
+    #[test]
+    fn test_initial_liquidity() {
+        assert_eq!(integer_sqrt(0), 0);
+        assert_eq!(integer_sqrt(15), 3);
+        assert_eq!(integer_sqrt(16), 4);
+        assert_eq!(integer_sqrt(u128::MAX), u64::MAX as u128);
+        assert_eq!(initial_liquidity(u64::MAX, u64::MAX), Some(u64::MAX));
+    }
+
+    proptest! {
+        #[test]
+        fn prop_ceil_div_matches_program(dividend in any::<u64>(), divisor in 1..u64::MAX) {
+            let expected = U128::from(dividend)
+                .checked_ceil_div(U128::from(divisor))
+                .map(|(quotient, _)| quotient.as_u128());
+            prop_assert_eq!(ceil_div(dividend as u128, divisor as u128), expected);
+        }
+
+        #[test]
+        fn prop_swap_base_in_matches_program(
+            amount_in in 1..u64::MAX / 4,
+            total_coin in 1..u64::MAX / 4,
+            total_pc in 1..u64::MAX / 4,
+        ) {
+            let expected = Calculator::swap_token_amount_base_in(
+                U128::from(amount_in),
+                U128::from(total_pc),
+                U128::from(total_coin),
+                SwapDirection::Coin2PC,
+            )
+            .as_u64();
+            prop_assert_eq!(
+                swap_base_in_amount_out(amount_in, total_coin, total_pc),
+                Some(expected)
+            );
+        }
+
+        #[test]
+        fn prop_swap_base_out_matches_program(
+            total_coin in 1..u64::MAX / 4,
+            total_pc in 2..u64::MAX / 4,
+            share in 1..10_000u64,
+        ) {
+            let amount_out = (total_pc as u128 * share as u128 / 10_000) as u64;
+            prop_assume!(amount_out > 0);
+            let expected = Calculator::swap_token_amount_base_out(
+                U128::from(amount_out),
+                U128::from(total_pc),
+                U128::from(total_coin),
+                SwapDirection::Coin2PC,
+            );
+            prop_assume!(expected <= U128::from(u64::MAX));
+            prop_assert_eq!(
+                swap_base_out_amount_in(amount_out, total_coin, total_pc),
+                Some(expected.as_u64())
+            );
+        }
+
+        #[test]
+        fn prop_initial_liquidity_matches_program(coin in any::<u64>(), pc in any::<u64>()) {
+            let expected = U128::from(pc).checked_mul(coin.into()).unwrap().integer_sqrt();
+            prop_assert_eq!(initial_liquidity(coin, pc), Some(expected.as_u64()));
+        }
+
+        #[test]
+        fn prop_swap_never_drains_the_pool(
+            amount_in in 1..u64::MAX / 4,
+            total_in in 1..u64::MAX / 4,
+            total_out in 1..u64::MAX / 4,
+        ) {
+            let amount_out = swap_base_in_amount_out(amount_in, total_in, total_out).unwrap();
+            prop_assert!(amount_out < total_out);
+            // the constant product never decreases
+            let k_before = total_in as u128 * total_out as u128;
+            let k_after = (total_in as u128 + amount_in as u128) * (total_out - amount_out) as u128;
+            prop_assert!(k_after >= k_before);
+        }
+    }
+}
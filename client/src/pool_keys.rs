@@ -0,0 +1,198 @@
+use anyhow::{Context, Result};
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+use raydium_puppet::constants::{
+    AMM_ASSOCIATED_SEED, AMM_AUTHORITY_SEED, AMM_CONFIG_SEED, AMM_V4_PROGRAM_IDS,
+    COIN_VAULT_ASSOCIATED_SEED, LP_MINT_ASSOCIATED_SEED, OPEN_ORDER_ASSOCIATED_SEED,
+    PC_VAULT_ASSOCIATED_SEED, TARGET_ASSOCIATED_SEED, TEMP_LP_TOKEN_ASSOCIATED_SEED,
+    WITHDRAW_ASSOCIATED_SEED,
+};
+use solana_sdk::pubkey;
+
+use crate::cache::{Market, Pool};
+
+// Receivers of the pool creation fee, same order as AMM_V4_PROGRAM_IDS
+pub const CREATE_POOL_FEE_DESTINATIONS: [Pubkey; 2] = [
+    pubkey!("7YttLkHDoNj9wyDur5pM1ejNaAvT9X4eqaYcHQqtj2G5"), // mainnet-beta
+    pubkey!("3XMrhbv989VxAMi3DErLV9eJht1pHppW5LbKxe9fkEFR"), // devnet
+];
+
+// OpenBook picks the first nonce that yields a valid program address
+const MAX_VAULT_SIGNER_NONCE: u64 = 255;
+
+/// Every address needed to build AMM v4 instructions for a pool, no RPC involved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolKeys {
+    pub amm_program: Pubkey,
+    pub amm_id: Pubkey,
+    pub amm_authority: Pubkey,
+    pub amm_open_orders: Pubkey,
+    pub amm_target_orders: Pubkey,
+    pub lp_mint: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub market_program: Pubkey,
+    pub market_id: Pubkey,
+    pub market_bids: Pubkey,
+    pub market_asks: Pubkey,
+    pub market_event_queue: Pubkey,
+    pub market_coin_vault: Pubkey,
+    pub market_pc_vault: Pubkey,
+    pub market_vault_signer: Pubkey,
+}
+
+impl PoolKeys {
+    pub fn from_cache(pool: &Pool, market: &Market) -> Result<Self> {
+        let market_program = parse(&pool.market_program_id)?;
+        let market_id = parse(&pool.market_id)?;
+        Ok(PoolKeys {
+            amm_program: parse(&pool.program_id)?,
+            amm_id: parse(&pool.amm_id)?,
+            amm_authority: parse(&pool.amm_authority)?,
+            amm_open_orders: parse(&pool.amm_open_orders)?,
+            amm_target_orders: parse(&pool.amm_target_orders)?,
+            lp_mint: parse(&pool.lp_mint)?,
+            coin_mint: parse(&pool.coin_mint)?,
+            pc_mint: parse(&pool.pc_mint)?,
+            coin_vault: parse(&pool.coin_vault)?,
+            pc_vault: parse(&pool.pc_vault)?,
+            market_program,
+            market_id,
+            market_bids: parse(&market.bids)?,
+            market_asks: parse(&market.asks)?,
+            market_event_queue: parse(&market.event_queue)?,
+            market_coin_vault: parse(&market.base_vault)?,
+            market_pc_vault: parse(&market.quote_vault)?,
+            market_vault_signer: find_vault_signer(&market_id, &market_program)?,
+        })
+    }
+}
+
+pub fn find_vault_signer(market_id: &Pubkey, market_program: &Pubkey) -> Result<Pubkey> {
+    find_vault_signer_and_nonce(market_id, market_program).map(|(vault_signer, _)| vault_signer)
+}
+
+// The nonce is stored in the market state, `initialize_market` needs it
+pub fn find_vault_signer_and_nonce(
+    market_id: &Pubkey,
+    market_program: &Pubkey,
+) -> Result<(Pubkey, u64)> {
+    (0..MAX_VAULT_SIGNER_NONCE)
+        .find_map(|nonce| {
+            Pubkey::create_program_address(
+                &[market_id.as_ref(), &nonce.to_le_bytes()],
+                market_program,
+            )
+            .ok()
+            .map(|vault_signer| (vault_signer, nonce))
+        })
+        .ok_or_else(|| anyhow::anyhow!("No vault signer found for market {}", market_id))
+}
+
+// Every pool account except the authority is a PDA of [amm_program, market, seed]
+pub fn find_amm_associated_address(
+    amm_program: &Pubkey,
+    market_id: &Pubkey,
+    seed: &[u8],
+) -> Pubkey {
+    Pubkey::find_program_address(
+        &[amm_program.as_ref(), market_id.as_ref(), seed],
+        amm_program,
+    )
+    .0
+}
+
+// Shared by all pools of the program, the bump is passed to initialize2 as the nonce
+pub fn find_amm_authority(amm_program: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[AMM_AUTHORITY_SEED], amm_program)
+}
+
+pub fn find_amm_config(amm_program: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[AMM_CONFIG_SEED], amm_program).0
+}
+
+pub fn find_create_pool_fee_destination(amm_program: &Pubkey) -> Option<Pubkey> {
+    AMM_V4_PROGRAM_IDS
+        .iter()
+        .position(|program| program == amm_program)
+        .map(|index| CREATE_POOL_FEE_DESTINATIONS[index])
+}
+
+// Every address of the pool on `market_id`, deterministic so no cache or RPC is needed.
+// The fee destination is left empty for deployments we don't know.
+pub fn derive_pool(
+    amm_program: &Pubkey,
+    market_program: &Pubkey,
+    market_id: &Pubkey,
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+) -> Pool {
+    let derive =
+        |seed: &[u8]| find_amm_associated_address(amm_program, market_id, seed).to_string();
+    Pool {
+        program_id: amm_program.to_string(),
+        amm_id: derive(AMM_ASSOCIATED_SEED),
+        amm_authority: find_amm_authority(amm_program).0.to_string(),
+        amm_open_orders: derive(OPEN_ORDER_ASSOCIATED_SEED),
+        lp_mint: derive(LP_MINT_ASSOCIATED_SEED),
+        coin_mint: coin_mint.to_string(),
+        pc_mint: pc_mint.to_string(),
+        coin_vault: derive(COIN_VAULT_ASSOCIATED_SEED),
+        pc_vault: derive(PC_VAULT_ASSOCIATED_SEED),
+        withdraw_queue: derive(WITHDRAW_ASSOCIATED_SEED),
+        amm_target_orders: derive(TARGET_ASSOCIATED_SEED),
+        pool_temp_lp: derive(TEMP_LP_TOKEN_ASSOCIATED_SEED),
+        market_program_id: market_program.to_string(),
+        market_id: market_id.to_string(),
+        amm_config_id: find_amm_config(amm_program).to_string(),
+        fee_destination_id: find_create_pool_fee_destination(amm_program)
+            .map(|address| address.to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn parse(address: &str) -> Result<Pubkey> {
+    Pubkey::from_str(address).with_context(|| format!("Invalid address: {}", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pool_keys_from_cache() {
+        let market = Market::read_from_cache().unwrap();
+        let pool = Pool::read_from_cache(&market.market_id).unwrap();
+
+        let keys = PoolKeys::from_cache(&pool, &market);
+        assert!(
+            keys.is_ok(),
+            "PoolKeys::from_cache failed with error: {:?}",
+            keys.unwrap_err()
+        );
+        let keys = keys.unwrap();
+        assert_eq!(keys.amm_id.to_string(), pool.amm_id);
+        assert_eq!(keys.market_id.to_string(), market.market_id);
+    }
+
+    #[test]
+    fn test_derive_pool_matches_cache() {
+        let market = Market::read_from_cache().unwrap();
+        let pool = Pool::read_from_cache(&market.market_id).unwrap();
+
+        let derived = derive_pool(
+            &parse(&pool.program_id).unwrap(),
+            &parse(&pool.market_program_id).unwrap(),
+            &parse(&pool.market_id).unwrap(),
+            &parse(&pool.coin_mint).unwrap(),
+            &parse(&pool.pc_mint).unwrap(),
+        );
+        assert_eq!(
+            derived, pool,
+            "Derived pool should match the one written by the SDK"
+        );
+    }
+}
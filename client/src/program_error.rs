@@ -0,0 +1,147 @@
+use num_traits::FromPrimitive;
+use raydium_amm::error::AmmError;
+use solana_sdk::pubkey::Pubkey;
+use spl_token::error::TokenError;
+use std::str::FromStr;
+use thiserror::Error;
+
+const FAILED_MARKER: &str = " failed: custom program error: 0x";
+
+// Custom errors raised by the programs a liquidity transaction goes through
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PoolProgramError {
+    #[error("Pool price moved past the requested bounds, retry with a wider slippage")]
+    ExceededSlippage,
+
+    #[error("Pool status does not allow this instruction, it may be disabled or not open yet")]
+    InvalidPoolStatus,
+
+    #[error("Not enough tokens in the source account")]
+    InsufficientFunds,
+
+    #[error("Token account is not owned by the signing wallet")]
+    TokenOwnerMismatch,
+
+    #[error("Token account mint does not match the pool mint")]
+    TokenMintMismatch,
+
+    #[error("Raydium AMM error: {0}")]
+    Amm(AmmError),
+
+    #[error("SPL token error: {0}")]
+    Token(TokenError),
+
+    #[error("Program {program} failed with custom error {code:#x}")]
+    Unknown { program: Pubkey, code: u32 },
+}
+
+impl PoolProgramError {
+    // `amm_program` is the AMM v4 program the client is configured with
+    pub fn decode(program: &Pubkey, code: u32, amm_program: &Pubkey) -> Self {
+        if program == amm_program {
+            return match AmmError::from_u32(code) {
+                Some(AmmError::ExceededSlippage) => Self::ExceededSlippage,
+                Some(AmmError::InvalidStatus) => Self::InvalidPoolStatus,
+                Some(AmmError::InsufficientFunds) => Self::InsufficientFunds,
+                Some(error) => Self::Amm(error),
+                None => Self::Unknown {
+                    program: *program,
+                    code,
+                },
+            };
+        }
+        if *program == spl_token::id() {
+            return match TokenError::from_u32(code) {
+                Some(TokenError::InsufficientFunds) => Self::InsufficientFunds,
+                Some(TokenError::OwnerMismatch) => Self::TokenOwnerMismatch,
+                Some(TokenError::MintMismatch) => Self::TokenMintMismatch,
+                Some(error) => Self::Token(error),
+                None => Self::Unknown {
+                    program: *program,
+                    code,
+                },
+            };
+        }
+        Self::Unknown {
+            program: *program,
+            code,
+        }
+    }
+
+    pub fn from_logs(logs: &[String], amm_program: &Pubkey) -> Option<Self> {
+        find_custom_error(logs).map(|(program, code)| Self::decode(&program, code, amm_program))
+    }
+
+    pub fn is_slippage(&self) -> bool {
+        matches!(self, Self::ExceededSlippage)
+    }
+}
+
+// The innermost failing program logs first, outer CPI callers repeat the same code afterwards
+pub fn find_custom_error(logs: &[String]) -> Option<(Pubkey, u32)> {
+    logs.iter().find_map(|log| {
+        let (program, code) = log.strip_prefix("Program ")?.split_once(FAILED_MARKER)?;
+        let program = Pubkey::from_str(program).ok()?;
+        let code = u32::from_str_radix(code.trim(), 16).ok()?;
+        Some((program, code))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use raydium_puppet::constants::AMM_V4_PROGRAM_IDS;
+
+    #[test]
+    fn test_find_custom_error_returns_innermost_program() {
+        let amm = Pubkey::new_unique();
+        let puppet = Pubkey::new_unique();
+        let logs = vec![
+            format!("Program {} invoke [1]", puppet),
+            format!("Program {} invoke [2]", amm),
+            "Program log: Error: ExceededSlippage".to_string(),
+            format!("Program {} failed: custom program error: 0x1e", amm),
+            format!("Program {} failed: custom program error: 0x1e", puppet),
+        ];
+        assert_eq!(find_custom_error(&logs), Some((amm, 0x1e)));
+        assert_eq!(find_custom_error(&logs[..3]), None);
+    }
+
+    #[test]
+    fn test_decode_program_errors() {
+        let amm = AMM_V4_PROGRAM_IDS[1];
+        assert_eq!(
+            PoolProgramError::decode(&amm, AmmError::ExceededSlippage as u32, &amm),
+            PoolProgramError::ExceededSlippage
+        );
+        assert_eq!(
+            PoolProgramError::decode(&amm, AmmError::InvalidStatus as u32, &amm),
+            PoolProgramError::InvalidPoolStatus
+        );
+        assert_eq!(
+            PoolProgramError::decode(&spl_token::id(), TokenError::InsufficientFunds as u32, &amm),
+            PoolProgramError::InsufficientFunds
+        );
+        assert_eq!(
+            PoolProgramError::decode(&spl_token::id(), TokenError::AccountFrozen as u32, &amm),
+            PoolProgramError::Token(TokenError::AccountFrozen)
+        );
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            PoolProgramError::decode(&other, 7, &amm),
+            PoolProgramError::Unknown {
+                program: other,
+                code: 7
+            }
+        );
+        // an AMM v4 deployment the client is not configured with is not decoded as the AMM
+        let unconfigured = AMM_V4_PROGRAM_IDS[0];
+        assert_eq!(
+            PoolProgramError::decode(&unconfigured, AmmError::ExceededSlippage as u32, &amm),
+            PoolProgramError::Unknown {
+                program: unconfigured,
+                code: AmmError::ExceededSlippage as u32
+            }
+        );
+    }
+}
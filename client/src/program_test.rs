@@ -0,0 +1,1206 @@
+// Offline test harness: boots a BanksClient with the AMM v4, OpenBook and puppet programs loaded,
+// then creates a market and a pool on fresh mints with the client itself, so liquidity code can
+// be tested without an RPC or any dumped account.
+//
+// - AMM v4 and OpenBook run natively from the `raydium_amm` and `serum_dex` crates. Both are built
+//   without their devnet feature, so they only accept the mainnet program ids.
+// - The puppet is the BPF build of `anchor build`, picked up from `SBF_OUT_DIR`, `BPF_OUT_DIR` or
+//   `target/deploy`. It is deployed upgradeable with `admin` as upgrade authority, so
+//   `initialize_config` runs its real check.
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anyhow::Result;
+use raydium_puppet::constants::{VAULT_POSITION_SEED, VAULT_SEED};
+use raydium_puppet::errors::PuppetError;
+use solana_banks_client::BanksClientError;
+use solana_program_test::{processor, ProgramTest, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::bpf_loader_upgradeable::{self, UpgradeableLoaderState};
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::native_token::LAMPORTS_PER_SOL;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::program_option::COption;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_program;
+use solana_sdk::transaction::{Transaction, TransactionError};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::str::FromStr;
+use tokio::runtime::Runtime;
+
+use crate::backend::{BanksBackend, TransactionBackend};
+use crate::cache::{Market, Pool};
+use crate::client_helper::ClientHelper;
+use crate::cluster::Cluster;
+use crate::compute_budget::ComputeBudgetConfig;
+use crate::confirmation::ConfirmationConfig;
+use crate::create_market::{
+    build_create_market_ix, CreateMarketConfig, DEFAULT_QUOTE_DUST_THRESHOLD,
+};
+use crate::create_pool::{build_create_pool_ix, CreatePoolConfig};
+use crate::fetch_pool::ExtendedAmmInfo;
+use crate::pool_keys::{find_amm_config, find_create_pool_fee_destination, PoolKeys};
+
+const AMM_PROGRAM_NAME: &str = "raydium_amm";
+const OPENBOOK_PROGRAM_NAME: &str = "openbook_dex";
+const PUPPET_PROGRAM_NAME: &str = "raydium_puppet";
+
+const USER_LAMPORTS: u64 = 100 * LAMPORTS_PER_SOL;
+pub const USER_TOKEN_AMOUNT: u64 = 1_000_000_000_000;
+pub const TOKEN_DECIMALS: u8 = 6;
+// initial liquidity of the harness pool, 1_000 tokens on each side
+pub const POOL_TOKEN_AMOUNT: u64 = 1_000_000_000;
+
+pub struct Harness {
+    pub context: ProgramTestContext,
+    pub backend: Rc<BanksBackend>,
+    pub user: Keypair,
+    // funded wallet without tokens, pays fees and rent for the user in sponsored flows
+    pub sponsor: Keypair,
+    // upgrade authority of the puppet, `start` makes it the config authority
+    pub admin: Keypair,
+    pub market: Market,
+    pub pool: Pool,
+    pub pool_keys: PoolKeys,
+}
+
+impl Harness {
+    // Bank with the harness pool and a puppet config allowing it
+    pub fn start() -> Result<Self> {
+        let harness = Self::start_uninitialized()?;
+        let admin = harness.admin.pubkey();
+        harness.process_transaction_signed_by(
+            &[
+                harness.initialize_config_ix(&admin),
+                harness.update_config_ix(
+                    &admin,
+                    raydium_puppet::instruction::AllowPool {
+                        amm: harness.pool_keys.amm_id,
+                    },
+                ),
+            ],
+            &harness.admin,
+        )?;
+        Ok(harness)
+    }
+
+    // Bank with the harness pool, the puppet config is left to the test
+    pub fn start_uninitialized() -> Result<Self> {
+        let amm_program = Cluster::MainnetBeta.amm_program();
+        let market_program = Cluster::MainnetBeta.market_program();
+
+        let mut program_test = ProgramTest::default();
+        program_test.add_program(
+            AMM_PROGRAM_NAME,
+            amm_program,
+            processor!(raydium_amm::processor::Processor::process),
+        );
+        program_test.add_program(
+            OPENBOOK_PROGRAM_NAME,
+            market_program,
+            processor!(process_openbook_instruction),
+        );
+        let admin = Keypair::new();
+        for (address, account) in puppet_program_accounts(&admin.pubkey())? {
+            program_test.add_account(address, account);
+        }
+
+        // zeroed AMM config, creating a pool costs no fee
+        program_test.add_account(
+            find_amm_config(&amm_program),
+            program_account(
+                vec![0u8; std::mem::size_of::<raydium_amm::state::AmmConfig>()],
+                &amm_program,
+            ),
+        );
+
+        // a funded user holding both sides of the pool
+        let user = Keypair::new();
+        let sponsor = Keypair::new();
+        let fee_destination = find_create_pool_fee_destination(&amm_program)
+            .ok_or_else(|| anyhow::anyhow!("No pool fee destination for {}", amm_program))?;
+        for wallet in [
+            user.pubkey(),
+            sponsor.pubkey(),
+            admin.pubkey(),
+            fee_destination,
+        ] {
+            program_test.add_account(
+                wallet,
+                Account::new(USER_LAMPORTS, 0, &system_program::id()),
+            );
+        }
+        let coin_mint = Pubkey::new_unique();
+        let pc_mint = Pubkey::new_unique();
+        for mint in [coin_mint, pc_mint] {
+            program_test.add_account(mint, mint_account(USER_TOKEN_AMOUNT));
+            program_test.add_account(
+                get_associated_token_address(&user.pubkey(), &mint),
+                token_account(&mint, &user.pubkey(), USER_TOKEN_AMOUNT),
+            );
+        }
+
+        // the bank is served on this runtime, so the backend has to keep driving it
+        let runtime = Runtime::new()?;
+        let context = runtime.block_on(program_test.start_with_context());
+        let backend = Rc::new(BanksBackend::new(runtime, context.banks_client.clone()));
+
+        // the market and the pool go through the same instructions as on a cluster
+        let client = client_helper(&backend, &user);
+        let market = build_create_market_ix(
+            &client,
+            &CreateMarketConfig {
+                market_program,
+                base_mint: coin_mint,
+                base_decimals: TOKEN_DECIMALS,
+                quote_mint: pc_mint,
+                quote_decimals: TOKEN_DECIMALS,
+                lot_size: 1.0,
+                tick_size: 0.01,
+                quote_dust_threshold: DEFAULT_QUOTE_DUST_THRESHOLD,
+            },
+        )?;
+        client.process_transaction(&market.vaults, false)?;
+        client.process_transaction(&market.market, false)?;
+        let market = market.accounts;
+        let (instructions, pool) = build_create_pool_ix(
+            &client,
+            &CreatePoolConfig {
+                amm_program,
+                market_program,
+                market_id: Pubkey::from_str(&market.market_id)?,
+                coin_amount: POOL_TOKEN_AMOUNT,
+                pc_amount: POOL_TOKEN_AMOUNT,
+                open_time: 0,
+            },
+        )?;
+        client.process_transaction(&instructions, false)?;
+        let pool_keys = PoolKeys::from_cache(&pool, &market)?;
+
+        Ok(Self {
+            context,
+            backend,
+            user,
+            sponsor,
+            admin,
+            market,
+            pool,
+            pool_keys,
+        })
+    }
+
+    // ClientHelper wired to the harness user and sending through the bank
+    pub fn client_helper(&self) -> ClientHelper {
+        client_helper(&self.backend, &self.user)
+    }
+
+    // Same user, with the sponsor as fee payer
+    pub fn sponsored_client_helper(&self) -> ClientHelper {
+        let sponsor = Rc::new(Keypair::from_bytes(&self.sponsor.to_bytes()).unwrap());
+        let fee_payer = sponsor as Rc<dyn Signer>;
+        ClientHelper {
+            payer: Rc::clone(&fee_payer),
+            signing_keypairs: vec![fee_payer],
+            ..self.client_helper()
+        }
+    }
+
+    pub fn fetch_lamports(&self, address: &Pubkey) -> Result<u64> {
+        Ok(self.client_helper().get_account(address)?.lamports)
+    }
+
+    pub fn process_transaction(&self, instructions: &[Instruction]) -> Result<()> {
+        self.process_transaction_signed_by(instructions, &self.user)
+    }
+
+    // `signer` pays and signs instead of the user, the bank error is kept for `assert_puppet_error`
+    pub fn process_transaction_signed_by(
+        &self,
+        instructions: &[Instruction],
+        signer: &Keypair,
+    ) -> Result<()> {
+        let blockhash = self.backend.get_latest_blockhash()?;
+        let txn = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&signer.pubkey()),
+            &[signer],
+            blockhash,
+        );
+        self.backend.send_transaction(&txn)?;
+        Ok(())
+    }
+
+    pub fn fetch_user_balance(&self, mint: &Pubkey) -> Result<u64> {
+        let ata = get_associated_token_address(&self.user.pubkey(), mint);
+        self.client_helper().fetch_token_balance(&ata)
+    }
+
+    pub fn fetch_extended_amm_info(&self) -> Result<ExtendedAmmInfo> {
+        self.client_helper()
+            .fetch_extended_amm_info(&self.pool_keys.amm_id)
+    }
+
+    pub fn fetch_puppet_account<T: anchor_lang::AccountDeserialize>(
+        &self,
+        address: &Pubkey,
+    ) -> Result<T> {
+        let account = self.client_helper().get_account(address)?;
+        Ok(T::try_deserialize(&mut account.data.as_slice())?)
+    }
+
+    pub fn initialize_config_ix(&self, authority: &Pubkey) -> Instruction {
+        let accounts = raydium_puppet::accounts::InitializeConfig {
+            config: puppet_config_address(),
+            authority: *authority,
+            program: raydium_puppet::ID,
+            program_data: bpf_loader_upgradeable::get_program_data_address(&raydium_puppet::ID),
+            system_program: system_program::id(),
+        };
+        puppet_ix(accounts, raydium_puppet::instruction::InitializeConfig {})
+    }
+
+    // Any of the UpdateConfig instructions: transfer_authority, set_paused, allow_pool, disallow_pool
+    pub fn update_config_ix(&self, authority: &Pubkey, data: impl InstructionData) -> Instruction {
+        let accounts = raydium_puppet::accounts::UpdateConfig {
+            config: puppet_config_address(),
+            authority: *authority,
+        };
+        puppet_ix(accounts, data)
+    }
+
+    // Pool side of the puppet liquidity instructions
+    pub fn deposit_pool_accounts(&self) -> raydium_puppet::accounts::DepositPool {
+        let keys = &self.pool_keys;
+        raydium_puppet::accounts::DepositPool {
+            config: puppet_config_address(),
+            amm_program: keys.amm_program,
+            amm: keys.amm_id,
+            amm_authority: keys.amm_authority,
+            amm_open_orders: keys.amm_open_orders,
+            amm_target_orders: keys.amm_target_orders,
+            amm_lp_mint: keys.lp_mint,
+            amm_coin_vault: keys.coin_vault,
+            amm_pc_vault: keys.pc_vault,
+            market: keys.market_id,
+            market_event_queue: keys.market_event_queue,
+        }
+    }
+
+    pub fn withdraw_pool_accounts(&self) -> raydium_puppet::accounts::WithdrawPool {
+        let keys = &self.pool_keys;
+        raydium_puppet::accounts::WithdrawPool {
+            config: puppet_config_address(),
+            amm_program: keys.amm_program,
+            amm: keys.amm_id,
+            amm_authority: keys.amm_authority,
+            amm_open_orders: keys.amm_open_orders,
+            amm_target_orders: keys.amm_target_orders,
+            amm_lp_mint: keys.lp_mint,
+            amm_coin_vault: keys.coin_vault,
+            amm_pc_vault: keys.pc_vault,
+            market_program: keys.market_program,
+            market: keys.market_id,
+            market_coin_vault: keys.market_coin_vault,
+            market_pc_vault: keys.market_pc_vault,
+            market_vault_signer: keys.market_vault_signer,
+            market_event_queue: keys.market_event_queue,
+            market_bids: keys.market_bids,
+            market_asks: keys.market_asks,
+        }
+    }
+}
+
+impl Harness {
+    // The user's side of the puppet deposit, on the harness pool
+    pub fn puppet_deposit_accounts(&self) -> raydium_puppet::accounts::PuppetDeposit {
+        let keys = &self.pool_keys;
+        let user = self.user.pubkey();
+        raydium_puppet::accounts::PuppetDeposit {
+            pool: self.deposit_pool_accounts(),
+            user_token_coin: get_associated_token_address(&user, &keys.coin_mint),
+            user_token_pc: get_associated_token_address(&user, &keys.pc_mint),
+            user_token_lp: get_associated_token_address(&user, &keys.lp_mint),
+            user_owner: user,
+            token_program: spl_token::id(),
+        }
+    }
+
+    pub fn puppet_withdraw_accounts(&self) -> raydium_puppet::accounts::PuppetWithdraw {
+        let keys = &self.pool_keys;
+        let user = self.user.pubkey();
+        raydium_puppet::accounts::PuppetWithdraw {
+            pool: self.withdraw_pool_accounts(),
+            user_token_lp: get_associated_token_address(&user, &keys.lp_mint),
+            user_token_coin: get_associated_token_address(&user, &keys.coin_mint),
+            user_token_pc: get_associated_token_address(&user, &keys.pc_mint),
+            user_owner: user,
+            token_program: spl_token::id(),
+        }
+    }
+
+    pub fn puppet_deposit_ix(&self, max_coin_amount: u64, max_pc_amount: u64) -> Instruction {
+        puppet_ix(
+            self.puppet_deposit_accounts(),
+            raydium_puppet::instruction::Deposit {
+                max_coin_amount,
+                max_pc_amount,
+                base_side: 0,
+            },
+        )
+    }
+
+    pub fn puppet_withdraw_ix(&self, amount: u64) -> Instruction {
+        puppet_ix(
+            self.puppet_withdraw_accounts(),
+            raydium_puppet::instruction::Withdraw { amount },
+        )
+    }
+
+    // Puppet swap selling `input_mint` into the user's `output_mint` account
+    pub fn puppet_swap_accounts(
+        &self,
+        input_mint: &Pubkey,
+        output_mint: &Pubkey,
+    ) -> raydium_puppet::accounts::PuppetSwap {
+        let keys = &self.pool_keys;
+        let user = self.user.pubkey();
+        raydium_puppet::accounts::PuppetSwap {
+            config: puppet_config_address(),
+            amm_program: keys.amm_program,
+            amm: keys.amm_id,
+            amm_authority: keys.amm_authority,
+            amm_open_orders: keys.amm_open_orders,
+            amm_coin_vault: keys.coin_vault,
+            amm_pc_vault: keys.pc_vault,
+            market_program: keys.market_program,
+            market: keys.market_id,
+            market_bids: keys.market_bids,
+            market_asks: keys.market_asks,
+            market_event_queue: keys.market_event_queue,
+            market_coin_vault: keys.market_coin_vault,
+            market_pc_vault: keys.market_pc_vault,
+            market_vault_signer: keys.market_vault_signer,
+            user_token_source: get_associated_token_address(&user, input_mint),
+            user_token_destination: get_associated_token_address(&user, output_mint),
+            user_source_owner: user,
+            token_program: spl_token::id(),
+        }
+    }
+
+    pub fn vault_address(&self) -> Pubkey {
+        Pubkey::find_program_address(
+            &[VAULT_SEED, self.pool_keys.amm_id.as_ref()],
+            &raydium_puppet::ID,
+        )
+        .0
+    }
+
+    pub fn vault_position_address(&self, owner: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[
+                VAULT_POSITION_SEED,
+                self.vault_address().as_ref(),
+                owner.as_ref(),
+            ],
+            &raydium_puppet::ID,
+        )
+        .0
+    }
+
+    // Vault token account of `mint`
+    pub fn vault_token_address(&self, mint: &Pubkey) -> Pubkey {
+        get_associated_token_address(&self.vault_address(), mint)
+    }
+
+    // Opens the harness pool vault, the user pays
+    pub fn open_vault_ix(&self) -> Instruction {
+        let keys = &self.pool_keys;
+        let accounts = raydium_puppet::accounts::OpenVault {
+            pool: self.deposit_pool_accounts(),
+            vault: self.vault_address(),
+            coin_mint: keys.coin_mint,
+            pc_mint: keys.pc_mint,
+            lp_mint: keys.lp_mint,
+            vault_token_coin: self.vault_token_address(&keys.coin_mint),
+            vault_token_pc: self.vault_token_address(&keys.pc_mint),
+            vault_token_lp: self.vault_token_address(&keys.lp_mint),
+            payer: self.user.pubkey(),
+            token_program: spl_token::id(),
+            associated_token_program: spl_associated_token_account::id(),
+            system_program: system_program::id(),
+        };
+        puppet_ix(accounts, raydium_puppet::instruction::OpenVault {})
+    }
+
+    pub fn open_vault_position_ix(&self, owner: &Pubkey) -> Instruction {
+        let accounts = raydium_puppet::accounts::OpenVaultPosition {
+            vault: self.vault_address(),
+            position: self.vault_position_address(owner),
+            owner: *owner,
+            system_program: system_program::id(),
+        };
+        puppet_ix(accounts, raydium_puppet::instruction::OpenVaultPosition {})
+    }
+
+    // Accounts of vault_fund and vault_reclaim
+    pub fn vault_transfer_accounts(
+        &self,
+        owner: &Pubkey,
+    ) -> raydium_puppet::accounts::VaultTransfer {
+        let keys = &self.pool_keys;
+        raydium_puppet::accounts::VaultTransfer {
+            vault: self.vault_address(),
+            position: self.vault_position_address(owner),
+            vault_token_coin: self.vault_token_address(&keys.coin_mint),
+            vault_token_pc: self.vault_token_address(&keys.pc_mint),
+            owner_token_coin: get_associated_token_address(owner, &keys.coin_mint),
+            owner_token_pc: get_associated_token_address(owner, &keys.pc_mint),
+            owner: *owner,
+            token_program: spl_token::id(),
+        }
+    }
+
+    pub fn vault_deposit_ix(
+        &self,
+        owner: &Pubkey,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+    ) -> Instruction {
+        let keys = &self.pool_keys;
+        let accounts = raydium_puppet::accounts::VaultDeposit {
+            pool: self.deposit_pool_accounts(),
+            vault: self.vault_address(),
+            position: self.vault_position_address(owner),
+            vault_token_coin: self.vault_token_address(&keys.coin_mint),
+            vault_token_pc: self.vault_token_address(&keys.pc_mint),
+            vault_token_lp: self.vault_token_address(&keys.lp_mint),
+            owner: *owner,
+            token_program: spl_token::id(),
+        };
+        puppet_ix(
+            accounts,
+            raydium_puppet::instruction::VaultDeposit {
+                max_coin_amount,
+                max_pc_amount,
+                base_side: 0,
+            },
+        )
+    }
+
+    pub fn vault_withdraw_ix(&self, owner: &Pubkey, shares: u64) -> Instruction {
+        let keys = &self.pool_keys;
+        let accounts = raydium_puppet::accounts::VaultWithdraw {
+            pool: self.withdraw_pool_accounts(),
+            vault: self.vault_address(),
+            position: self.vault_position_address(owner),
+            vault_token_coin: self.vault_token_address(&keys.coin_mint),
+            vault_token_pc: self.vault_token_address(&keys.pc_mint),
+            vault_token_lp: self.vault_token_address(&keys.lp_mint),
+            owner: *owner,
+            token_program: spl_token::id(),
+        };
+        puppet_ix(
+            accounts,
+            raydium_puppet::instruction::VaultWithdraw { shares },
+        )
+    }
+
+    pub fn fetch_puppet_config(&self) -> Result<raydium_puppet::state::Config> {
+        self.fetch_puppet_account(&puppet_config_address())
+    }
+}
+
+pub fn puppet_config_address() -> Pubkey {
+    Pubkey::find_program_address(
+        &[raydium_puppet::constants::CONFIG_SEED],
+        &raydium_puppet::ID,
+    )
+    .0
+}
+
+pub fn puppet_ix(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: raydium_puppet::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+// Fails unless the bank rejected the transaction with `error` from the puppet
+pub fn assert_puppet_error(result: Result<()>, error: PuppetError) {
+    let err = result.expect_err("transaction should have failed");
+    let err = match err.downcast_ref::<BanksClientError>() {
+        Some(err) => err.unwrap(),
+        None => panic!("transaction failed outside of the bank: {:?}", err),
+    };
+    let expected = u32::from(error);
+    assert!(
+        matches!(
+            err,
+            TransactionError::InstructionError(_, InstructionError::Custom(code)) if code == expected
+        ),
+        "expected {:?} ({}), got {:?}",
+        error,
+        expected,
+        err
+    );
+}
+
+fn client_helper(backend: &Rc<BanksBackend>, user: &Keypair) -> ClientHelper {
+    let user_keypair = Rc::new(Keypair::from_bytes(&user.to_bytes()).unwrap());
+    let fee_payer = Rc::clone(&user_keypair) as Rc<dyn Signer>;
+    let config = raydium_library::common::CommonConfig::default();
+    ClientHelper {
+        backend: Rc::clone(backend) as Rc<dyn TransactionBackend>,
+        user_keypair,
+        payer: Rc::clone(&fee_payer),
+        signing_keypairs: vec![fee_payer],
+        config,
+        confirmation: ConfirmationConfig::default(),
+        compute_budget: ComputeBudgetConfig::default(),
+        cluster: Cluster::Localnet,
+        amm_program: Cluster::MainnetBeta.amm_program(),
+        market_program: Cluster::MainnetBeta.market_program(),
+        cache_dir: std::env::temp_dir(),
+    }
+}
+
+// serum_dex has its own error type, the runtime wants a ProgramError
+fn process_openbook_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    input: &[u8],
+) -> ProgramResult {
+    serum_dex::state::State::process(program_id, accounts, input).map_err(ProgramError::from)
+}
+
+// Program and program data accounts of an upgradeable deploy, as the upgradeable loader lays them out
+fn puppet_program_accounts(upgrade_authority: &Pubkey) -> Result<[(Pubkey, Account); 2]> {
+    let elf = read_program_file(PUPPET_PROGRAM_NAME)?;
+    let programdata_address = bpf_loader_upgradeable::get_program_data_address(&raydium_puppet::ID);
+    let program = bincode::serialize(&UpgradeableLoaderState::Program {
+        programdata_address,
+    })?;
+    let mut programdata = bincode::serialize(&UpgradeableLoaderState::ProgramData {
+        slot: 0,
+        upgrade_authority_address: Some(*upgrade_authority),
+    })?;
+    programdata.resize(UpgradeableLoaderState::size_of_programdata_metadata(), 0);
+    programdata.extend_from_slice(&elf);
+
+    let mut program = program_account(program, &bpf_loader_upgradeable::id());
+    program.executable = true;
+    Ok([
+        (raydium_puppet::ID, program),
+        (
+            programdata_address,
+            program_account(programdata, &bpf_loader_upgradeable::id()),
+        ),
+    ])
+}
+
+fn read_program_file(name: &str) -> Result<Vec<u8>> {
+    let file = format!("{}.so", name);
+    let mut search = Vec::new();
+    for var in ["SBF_OUT_DIR", "BPF_OUT_DIR"] {
+        if let Ok(dir) = std::env::var(var) {
+            search.push(PathBuf::from(dir));
+        }
+    }
+    search.push(PathBuf::from("../target/deploy"));
+    match search
+        .iter()
+        .map(|dir| dir.join(&file))
+        .find(|path| path.exists())
+    {
+        Some(path) => Ok(std::fs::read(path)?),
+        None => Err(anyhow::anyhow!(
+            "{} not found in {:?}, run `anchor build` first",
+            file,
+            search
+        )),
+    }
+}
+
+fn mint_account(supply: u64) -> Account {
+    let mut data = vec![0u8; Mint::LEN];
+    Mint::pack(
+        Mint {
+            mint_authority: COption::None,
+            supply,
+            decimals: TOKEN_DECIMALS,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_account(data, &spl_token::id())
+}
+
+fn token_account(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+    let mut data = vec![0u8; TokenAccount::LEN];
+    TokenAccount::pack(
+        TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_account(data, &spl_token::id())
+}
+
+fn program_account(data: Vec<u8>, owner: &Pubkey) -> Account {
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: *owner,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quote::{self, SwapDirection};
+    use raydium_puppet::instructions::AddRemoveBounds;
+    use raydium_puppet::state::{Vault, VaultPosition};
+
+    #[test]
+    fn test_offline_puppet_deposit() {
+        let harness = Harness::start().unwrap();
+        let lp_mint = harness.pool_keys.lp_mint;
+        let pre_lp = harness.fetch_user_balance(&lp_mint).unwrap();
+
+        // 1:1 pool, the pc side rounds up
+        let ix = harness.puppet_deposit_ix(1_000_000, 1_010_000);
+        let result = harness.process_transaction(&[ix]);
+        assert!(
+            result.is_ok(),
+            "puppet deposit failed with error: {:?}",
+            result.unwrap_err()
+        );
+        let after_lp = harness.fetch_user_balance(&lp_mint).unwrap();
+        assert!(after_lp > pre_lp, "User LP amount should have increased");
+    }
+
+    #[test]
+    fn test_offline_harness_config() {
+        let harness = Harness::start().unwrap();
+
+        let config = harness.fetch_puppet_config().unwrap();
+        assert_eq!(config.authority, harness.admin.pubkey());
+        assert!(!config.paused);
+        assert_eq!(config.allowed_pools, vec![harness.pool_keys.amm_id]);
+    }
+
+    #[test]
+    fn test_offline_initialize_config_requires_upgrade_authority() {
+        // SETUP
+        let harness = Harness::start_uninitialized().unwrap();
+        let user = harness.user.pubkey();
+        let admin = harness.admin.pubkey();
+
+        // EXECUTE
+        let result = harness.process_transaction(&[harness.initialize_config_ix(&user)]);
+        assert_puppet_error(result, PuppetError::Unauthorized);
+        let result = harness
+            .process_transaction_signed_by(&[harness.initialize_config_ix(&admin)], &harness.admin);
+        assert!(
+            result.is_ok(),
+            "initialize_config failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let config = harness.fetch_puppet_config().unwrap();
+        assert_eq!(config.authority, admin);
+        assert!(!config.paused);
+        assert!(config.allowed_pools.is_empty());
+    }
+
+    #[test]
+    fn test_offline_transfer_authority() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let user = harness.user.pubkey();
+        let admin = harness.admin.pubkey();
+        let transfer_to_user = |authority: &Pubkey| {
+            harness.update_config_ix(
+                authority,
+                raydium_puppet::instruction::TransferAuthority {
+                    new_authority: user,
+                },
+            )
+        };
+        let pause = |authority: &Pubkey| {
+            harness.update_config_ix(
+                authority,
+                raydium_puppet::instruction::SetPaused { paused: true },
+            )
+        };
+
+        // EXECUTE
+        let result = harness.process_transaction(&[transfer_to_user(&user)]);
+        assert_puppet_error(result, PuppetError::Unauthorized);
+        harness
+            .process_transaction_signed_by(&[transfer_to_user(&admin)], &harness.admin)
+            .unwrap();
+
+        // EXPECT
+        assert_eq!(harness.fetch_puppet_config().unwrap().authority, user);
+        let result = harness.process_transaction_signed_by(&[pause(&admin)], &harness.admin);
+        assert_puppet_error(result, PuppetError::Unauthorized);
+        harness.process_transaction(&[pause(&user)]).unwrap();
+        assert!(harness.fetch_puppet_config().unwrap().paused);
+    }
+
+    #[test]
+    fn test_offline_set_paused() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let admin = harness.admin.pubkey();
+        let set_paused = |paused| {
+            let ix =
+                harness.update_config_ix(&admin, raydium_puppet::instruction::SetPaused { paused });
+            harness.process_transaction_signed_by(&[ix], &harness.admin)
+        };
+
+        // EXECUTE
+        set_paused(true).unwrap();
+
+        // EXPECT
+        assert!(harness.fetch_puppet_config().unwrap().paused);
+        let result =
+            harness.process_transaction(&[harness.puppet_deposit_ix(1_000_000, 1_010_000)]);
+        assert_puppet_error(result, PuppetError::Paused);
+        let result = harness.process_transaction(&[harness.puppet_withdraw_ix(1_000_000)]);
+        assert_puppet_error(result, PuppetError::Paused);
+
+        // the amounts differ from the rejected deposit, the bank would drop a replayed transaction
+        set_paused(false).unwrap();
+        assert!(!harness.fetch_puppet_config().unwrap().paused);
+        harness
+            .process_transaction(&[harness.puppet_deposit_ix(2_000_000, 2_010_000)])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_offline_allow_disallow_pool() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let admin = harness.admin.pubkey();
+        let amm = harness.pool_keys.amm_id;
+        let update = |data: raydium_puppet::instruction::DisallowPool| {
+            let ix = harness.update_config_ix(&admin, data);
+            harness.process_transaction_signed_by(&[ix], &harness.admin)
+        };
+
+        // EXECUTE
+        let result = update(raydium_puppet::instruction::DisallowPool {
+            amm: Pubkey::new_unique(),
+        });
+        assert_puppet_error(result, PuppetError::PoolNotAllowed);
+        update(raydium_puppet::instruction::DisallowPool { amm }).unwrap();
+
+        // EXPECT
+        assert!(harness
+            .fetch_puppet_config()
+            .unwrap()
+            .allowed_pools
+            .is_empty());
+        let result =
+            harness.process_transaction(&[harness.puppet_deposit_ix(1_000_000, 1_010_000)]);
+        assert_puppet_error(result, PuppetError::PoolNotAllowed);
+        let result = harness.process_transaction(&[harness.puppet_withdraw_ix(1_000_000)]);
+        assert_puppet_error(result, PuppetError::PoolNotAllowed);
+
+        let allow =
+            harness.update_config_ix(&admin, raydium_puppet::instruction::AllowPool { amm });
+        harness
+            .process_transaction_signed_by(&[allow], &harness.admin)
+            .unwrap();
+        assert_eq!(
+            harness.fetch_puppet_config().unwrap().allowed_pools,
+            vec![amm]
+        );
+        harness
+            .process_transaction(&[harness.puppet_deposit_ix(2_000_000, 2_010_000)])
+            .unwrap();
+    }
+
+    #[test]
+    fn test_offline_puppet_swap_base_in() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let keys = harness.pool_keys.clone();
+        let swap_ix = |minimum_amount_out| {
+            puppet_ix(
+                harness.puppet_swap_accounts(&keys.coin_mint, &keys.pc_mint),
+                raydium_puppet::instruction::SwapBaseIn {
+                    amount_in: 1_000_000,
+                    minimum_amount_out,
+                },
+            )
+        };
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let quote =
+            quote::quote_swap_base_in(&pool_info, SwapDirection::CoinToPc, 1_000_000).unwrap();
+        let pre_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let pre_user_pc_amount = harness.fetch_user_balance(&keys.pc_mint).unwrap();
+
+        // EXECUTE
+        let result = harness.process_transaction(&[swap_ix(quote.amount_out + 1)]);
+        assert_puppet_error(result, PuppetError::SlippageExceeded);
+        let result = harness.process_transaction(&[swap_ix(quote.amount_out)]);
+        assert!(
+            result.is_ok(),
+            "puppet swap_base_in failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let after_user_pc_amount = harness.fetch_user_balance(&keys.pc_mint).unwrap();
+        assert_eq!(pre_user_coin_amount - after_user_coin_amount, 1_000_000);
+        assert_eq!(after_user_pc_amount - pre_user_pc_amount, quote.amount_out);
+    }
+
+    #[test]
+    fn test_offline_puppet_swap_base_out() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let keys = harness.pool_keys.clone();
+        let swap_ix = |max_amount_in| {
+            puppet_ix(
+                harness.puppet_swap_accounts(&keys.pc_mint, &keys.coin_mint),
+                raydium_puppet::instruction::SwapBaseOut {
+                    max_amount_in,
+                    amount_out: 1_000_000,
+                },
+            )
+        };
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let quote =
+            quote::quote_swap_base_out(&pool_info, SwapDirection::PcToCoin, 1_000_000).unwrap();
+        let pre_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let pre_user_pc_amount = harness.fetch_user_balance(&keys.pc_mint).unwrap();
+
+        // EXECUTE
+        let result = harness.process_transaction(&[swap_ix(quote.amount_in - 1)]);
+        assert_puppet_error(result, PuppetError::SlippageExceeded);
+        let result = harness.process_transaction(&[swap_ix(quote.amount_in)]);
+        assert!(
+            result.is_ok(),
+            "puppet swap_base_out failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let after_user_pc_amount = harness.fetch_user_balance(&keys.pc_mint).unwrap();
+        assert_eq!(after_user_coin_amount - pre_user_coin_amount, 1_000_000);
+        assert_eq!(pre_user_pc_amount - after_user_pc_amount, quote.amount_in);
+    }
+
+    #[test]
+    fn test_offline_puppet_swap_rejects_foreign_mint() {
+        let harness = Harness::start().unwrap();
+        let keys = harness.pool_keys.clone();
+
+        // the LP mint is not a side of the pool
+        let ix = puppet_ix(
+            harness.puppet_swap_accounts(&keys.coin_mint, &keys.lp_mint),
+            raydium_puppet::instruction::SwapBaseIn {
+                amount_in: 1_000_000,
+                minimum_amount_out: 0,
+            },
+        );
+        let result = harness.process_transaction(&[ix]);
+        assert_puppet_error(result, PuppetError::InvalidSwapMint);
+    }
+
+    #[test]
+    fn test_offline_puppet_add_remove_liquidity_bounds() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let keys = harness.pool_keys.clone();
+        let loose = AddRemoveBounds {
+            max_coin_in: 1_000_000,
+            max_pc_in: 1_010_000,
+            min_lp_minted: 1,
+            min_coin_out: 1,
+            min_pc_out: 1,
+        };
+        let add_remove_ix = |bounds| {
+            puppet_ix(
+                raydium_puppet::accounts::PuppetAddRemoveLiquidity {
+                    deposit: harness.puppet_deposit_accounts(),
+                    withdraw: harness.puppet_withdraw_accounts(),
+                },
+                raydium_puppet::instruction::AddRemoveLiquidity {
+                    max_coin_amount: 1_000_000,
+                    max_pc_amount: 1_010_000,
+                    base_side: 0,
+                    withdraw_lp_amount: 1_000_000,
+                    bounds,
+                },
+            )
+        };
+
+        // BEFORE
+        let pre_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let pre_user_pc_amount = harness.fetch_user_balance(&keys.pc_mint).unwrap();
+
+        // EXECUTE
+        for (bounds, error) in [
+            (
+                AddRemoveBounds {
+                    min_lp_minted: u64::MAX,
+                    ..loose
+                },
+                PuppetError::LpDeltaOutOfBounds,
+            ),
+            (
+                AddRemoveBounds {
+                    max_coin_in: 999_999,
+                    ..loose
+                },
+                PuppetError::CoinDeltaOutOfBounds,
+            ),
+            (
+                AddRemoveBounds {
+                    min_pc_out: u64::MAX,
+                    ..loose
+                },
+                PuppetError::PcDeltaOutOfBounds,
+            ),
+        ] {
+            let result = harness.process_transaction(&[add_remove_ix(bounds)]);
+            assert_puppet_error(result, error);
+        }
+        let result = harness.process_transaction(&[add_remove_ix(loose)]);
+        assert!(
+            result.is_ok(),
+            "puppet add_remove_liquidity failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT, the withdraw leg hands most of the deposit back
+        let after_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let after_user_pc_amount = harness.fetch_user_balance(&keys.pc_mint).unwrap();
+        assert!(pre_user_coin_amount - after_user_coin_amount < loose.max_coin_in);
+        assert!(pre_user_pc_amount - after_user_pc_amount < loose.max_pc_in);
+    }
+
+    #[test]
+    fn test_offline_vault_fund_and_reclaim() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let keys = harness.pool_keys.clone();
+        let user = harness.user.pubkey();
+        harness
+            .process_transaction(&[
+                harness.open_vault_ix(),
+                harness.open_vault_position_ix(&user),
+            ])
+            .unwrap();
+        let vault: Vault = harness
+            .fetch_puppet_account(&harness.vault_address())
+            .unwrap();
+        assert_eq!(vault.amm, keys.amm_id);
+        assert_eq!(vault.lp_mint, keys.lp_mint);
+        assert_eq!(vault.total_shares, 0);
+
+        // BEFORE
+        let pre_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        let client = harness.client_helper();
+
+        // EXECUTE
+        let fund = puppet_ix(
+            harness.vault_transfer_accounts(&user),
+            raydium_puppet::instruction::VaultFund {
+                coin_amount: 2_000_000,
+                pc_amount: 3_000_000,
+            },
+        );
+        harness.process_transaction(&[fund]).unwrap();
+        let reclaim = |coin_amount, pc_amount| {
+            puppet_ix(
+                harness.vault_transfer_accounts(&user),
+                raydium_puppet::instruction::VaultReclaim {
+                    coin_amount,
+                    pc_amount,
+                },
+            )
+        };
+        let result = harness.process_transaction(&[reclaim(0, 3_000_001)]);
+        assert_puppet_error(result, PuppetError::InsufficientVaultBalance);
+        harness.process_transaction(&[reclaim(500_000, 0)]).unwrap();
+
+        // EXPECT
+        let position: VaultPosition = harness
+            .fetch_puppet_account(&harness.vault_position_address(&user))
+            .unwrap();
+        assert_eq!(position.owner, user);
+        assert_eq!(position.coin_balance, 1_500_000);
+        assert_eq!(position.pc_balance, 3_000_000);
+        assert_eq!(position.shares, 0);
+        let vault_coin = client
+            .fetch_token_balance(&harness.vault_token_address(&keys.coin_mint))
+            .unwrap();
+        assert_eq!(vault_coin, 1_500_000);
+        let after_user_coin_amount = harness.fetch_user_balance(&keys.coin_mint).unwrap();
+        assert_eq!(pre_user_coin_amount - after_user_coin_amount, 1_500_000);
+    }
+
+    #[test]
+    fn test_offline_vault_deposit_and_withdraw() {
+        // SETUP, the sponsor gets tokens from the user to hold a second position
+        let harness = Harness::start().unwrap();
+        let keys = harness.pool_keys.clone();
+        let user = harness.user.pubkey();
+        let sponsor = harness.sponsor.pubkey();
+        let mut setup = vec![
+            harness.open_vault_ix(),
+            harness.open_vault_position_ix(&user),
+        ];
+        for mint in [keys.coin_mint, keys.pc_mint] {
+            setup.push(
+                spl_associated_token_account::instruction::create_associated_token_account(
+                    &user,
+                    &sponsor,
+                    &mint,
+                    &spl_token::id(),
+                ),
+            );
+            setup.push(
+                spl_token::instruction::transfer(
+                    &spl_token::id(),
+                    &get_associated_token_address(&user, &mint),
+                    &get_associated_token_address(&sponsor, &mint),
+                    &user,
+                    &[],
+                    10_000_000,
+                )
+                .unwrap(),
+            );
+        }
+        harness.process_transaction(&setup).unwrap();
+        let fund = |owner: &Pubkey| {
+            puppet_ix(
+                harness.vault_transfer_accounts(owner),
+                raydium_puppet::instruction::VaultFund {
+                    coin_amount: 5_000_000,
+                    pc_amount: 5_000_000,
+                },
+            )
+        };
+        harness.process_transaction(&[fund(&user)]).unwrap();
+        let client = harness.client_helper();
+        let vault_lp = || {
+            client
+                .fetch_token_balance(&harness.vault_token_address(&keys.lp_mint))
+                .unwrap()
+        };
+        let fetch_position = |owner: &Pubkey| -> VaultPosition {
+            harness
+                .fetch_puppet_account(&harness.vault_position_address(owner))
+                .unwrap()
+        };
+
+        // EXECUTE, the first deposit gets one share per LP
+        let result =
+            harness.process_transaction(&[harness.vault_deposit_ix(&user, 5_000_001, 5_000_000)]);
+        assert_puppet_error(result, PuppetError::InsufficientVaultBalance);
+        let result =
+            harness.process_transaction(&[harness.vault_deposit_ix(&user, 1_000_000, 1_010_000)]);
+        assert!(
+            result.is_ok(),
+            "vault deposit failed with error: {:?}",
+            result.unwrap_err()
+        );
+        let user_shares = fetch_position(&user).shares;
+        assert!(user_shares > 0);
+        assert_eq!(user_shares, vault_lp());
+
+        // LP sent straight to the vault raises the value of every share
+        let donation = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &get_associated_token_address(&user, &keys.lp_mint),
+            &harness.vault_token_address(&keys.lp_mint),
+            &user,
+            &[],
+            user_shares,
+        )
+        .unwrap();
+        harness.process_transaction(&[donation]).unwrap();
+        let pre_vault_lp = vault_lp();
+        harness
+            .process_transaction_signed_by(
+                &[harness.open_vault_position_ix(&sponsor), fund(&sponsor)],
+                &harness.sponsor,
+            )
+            .unwrap();
+        harness
+            .process_transaction_signed_by(
+                &[harness.vault_deposit_ix(&sponsor, 1_000_000, 1_010_000)],
+                &harness.sponsor,
+            )
+            .unwrap();
+
+        // EXPECT
+        let lp_minted = vault_lp() - pre_vault_lp;
+        let sponsor_shares = fetch_position(&sponsor).shares;
+        assert_eq!(
+            sponsor_shares as u128,
+            lp_minted as u128 * user_shares as u128 / pre_vault_lp as u128,
+            "shares should be minted pro rata to the LP already held"
+        );
+        let vault: Vault = harness
+            .fetch_puppet_account(&harness.vault_address())
+            .unwrap();
+        assert_eq!(vault.total_shares, user_shares + sponsor_shares);
+
+        // EXECUTE, the user burns every share for their part of the LP
+        let result =
+            harness.process_transaction(&[harness.vault_withdraw_ix(&user, user_shares + 1)]);
+        assert_puppet_error(result, PuppetError::InsufficientShares);
+        let pre_vault_lp = vault_lp();
+        let pre_position = fetch_position(&user);
+        harness
+            .process_transaction(&[harness.vault_withdraw_ix(&user, user_shares)])
+            .unwrap();
+
+        // EXPECT
+        let expected_lp =
+            (user_shares as u128 * pre_vault_lp as u128 / vault.total_shares as u128) as u64;
+        assert_eq!(pre_vault_lp - vault_lp(), expected_lp);
+        let position = fetch_position(&user);
+        assert_eq!(position.shares, 0);
+        assert!(position.coin_balance > pre_position.coin_balance);
+        assert!(position.pc_balance > pre_position.pc_balance);
+        let vault: Vault = harness
+            .fetch_puppet_account(&harness.vault_address())
+            .unwrap();
+        assert_eq!(vault.total_shares, sponsor_shares);
+    }
+}
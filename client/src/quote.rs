@@ -0,0 +1,232 @@
+// Quotes computed from an ExtendedAmmInfo snapshot with the same integer math as the AMM v4 program,
+// so bots can price deposits, withdrawals and swaps without a simulation round-trip.
+// All amounts are raw, with decimals.
+use crate::fetch_pool::ExtendedAmmInfo;
+use crate::math::{
+    add_swap_fee, mul_div_ceil, mul_div_floor, swap_base_in_amount_out, swap_base_out_amount_in,
+    swap_fee, BPS_DENOMINATOR,
+};
+
+// Side of the deposit whose amount is given exactly, the other side is paired at the pool ratio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DepositSide {
+    Coin,
+    Pc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapDirection {
+    CoinToPc,
+    PcToCoin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DepositQuote {
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    pub lp_amount: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WithdrawQuote {
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub direction: SwapDirection,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub fee_amount: u64,
+    // shortfall of the output against the spot price, fees excluded
+    pub price_impact_bps: u64,
+}
+
+// Pairs `amount` of `fixed_side` at the current ratio (rounded up) and mints the fixed side share of the supply (rounded down)
+pub fn quote_deposit(
+    pool_info: &ExtendedAmmInfo,
+    fixed_side: DepositSide,
+    amount: u64,
+) -> Option<DepositQuote> {
+    let (total_coin, total_pc) = pool_info.total_without_take_pnl();
+    let (total_fixed, total_other) = match fixed_side {
+        DepositSide::Coin => (total_coin, total_pc),
+        DepositSide::Pc => (total_pc, total_coin),
+    };
+    let other_amount = mul_div_ceil(amount, total_other, total_fixed)?;
+    let lp_amount = mul_div_floor(amount, pool_info.lp_amount, total_fixed)?;
+    let (coin_amount, pc_amount) = match fixed_side {
+        DepositSide::Coin => (amount, other_amount),
+        DepositSide::Pc => (other_amount, amount),
+    };
+    Some(DepositQuote {
+        coin_amount,
+        pc_amount,
+        lp_amount,
+    })
+}
+
+// Pro-rata share of the reserves paid out for burning `lp_amount`, rounded down
+pub fn quote_withdraw(pool_info: &ExtendedAmmInfo, lp_amount: u64) -> Option<WithdrawQuote> {
+    let (total_coin, total_pc) = pool_info.total_without_take_pnl();
+    Some(WithdrawQuote {
+        coin_amount: mul_div_floor(lp_amount, total_coin, pool_info.lp_amount)?,
+        pc_amount: mul_div_floor(lp_amount, total_pc, pool_info.lp_amount)?,
+    })
+}
+
+pub fn quote_swap_base_in(
+    pool_info: &ExtendedAmmInfo,
+    direction: SwapDirection,
+    amount_in: u64,
+) -> Option<SwapQuote> {
+    let (total_in, total_out) = swap_reserves(pool_info, direction);
+    let fees = &pool_info.amm_info.fees;
+    let fee_amount = swap_fee(
+        amount_in,
+        fees.swap_fee_numerator,
+        fees.swap_fee_denominator,
+    )?;
+    let amount_in_after_fee = amount_in.checked_sub(fee_amount)?;
+    let amount_out = swap_base_in_amount_out(amount_in_after_fee, total_in, total_out)?;
+    Some(SwapQuote {
+        direction,
+        amount_in,
+        amount_out,
+        fee_amount,
+        price_impact_bps: price_impact_bps(amount_in_after_fee, amount_out, total_in, total_out)?,
+    })
+}
+
+pub fn quote_swap_base_out(
+    pool_info: &ExtendedAmmInfo,
+    direction: SwapDirection,
+    amount_out: u64,
+) -> Option<SwapQuote> {
+    let (total_in, total_out) = swap_reserves(pool_info, direction);
+    let fees = &pool_info.amm_info.fees;
+    let amount_in_before_fee = swap_base_out_amount_in(amount_out, total_in, total_out)?;
+    let amount_in = add_swap_fee(
+        amount_in_before_fee,
+        fees.swap_fee_numerator,
+        fees.swap_fee_denominator,
+    )?;
+    Some(SwapQuote {
+        direction,
+        amount_in,
+        amount_out,
+        fee_amount: amount_in.checked_sub(amount_in_before_fee)?,
+        price_impact_bps: price_impact_bps(amount_in_before_fee, amount_out, total_in, total_out)?,
+    })
+}
+
+fn swap_reserves(pool_info: &ExtendedAmmInfo, direction: SwapDirection) -> (u64, u64) {
+    let (total_coin, total_pc) = pool_info.total_without_take_pnl();
+    match direction {
+        SwapDirection::CoinToPc => (total_coin, total_pc),
+        SwapDirection::PcToCoin => (total_pc, total_coin),
+    }
+}
+
+fn price_impact_bps(amount_in: u64, amount_out: u64, total_in: u64, total_out: u64) -> Option<u64> {
+    let spot_amount_out = mul_div_floor(amount_in, total_out, total_in)?;
+    if spot_amount_out == 0 {
+        return Some(0);
+    }
+    mul_div_floor(
+        spot_amount_out.saturating_sub(amount_out),
+        BPS_DENOMINATOR,
+        spot_amount_out,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use raydium_amm::math::{InvariantPool, InvariantToken, RoundDirection};
+    use raydium_amm::state::AmmInfo;
+
+    fn pool_info(total_coin: u64, total_pc: u64, lp_amount: u64) -> ExtendedAmmInfo {
+        let mut amm_info = AmmInfo::default();
+        amm_info.lp_amount = lp_amount;
+        amm_info.fees.swap_fee_numerator = 25;
+        amm_info.fees.swap_fee_denominator = 10_000;
+        ExtendedAmmInfo::new(amm_info, lp_amount, total_coin, total_pc)
+    }
+
+    #[test]
+    fn test_quote_swap_round_trip() {
+        let info = pool_info(1_000_000_000, 2_000_000_000, 1_000_000_000);
+        let base_in = quote_swap_base_in(&info, SwapDirection::CoinToPc, 1_000_000).unwrap();
+        assert_eq!(base_in.fee_amount, 2_500);
+        assert!(base_in.price_impact_bps <= 10);
+
+        // asking for the same output costs at most the original input
+        let base_out =
+            quote_swap_base_out(&info, SwapDirection::CoinToPc, base_in.amount_out).unwrap();
+        assert!(base_out.amount_in <= base_in.amount_in);
+    }
+
+    proptest! {
+        #[test]
+        fn prop_quote_deposit_matches_program(
+            total_coin in 1..u64::MAX / 4,
+            total_pc in 1..u64::MAX / 4,
+            lp_amount in 1..u64::MAX / 4,
+            amount in 1..u32::MAX as u64,
+        ) {
+            let info = pool_info(total_coin, total_pc, lp_amount);
+            let invariant = InvariantToken { token_coin: total_coin, token_pc: total_pc };
+            let expected_pc = invariant.exchange_coin_to_pc(amount, RoundDirection::Ceiling);
+            let expected_lp = InvariantPool { token_input: amount, token_total: total_coin }
+                .exchange_token_to_pool(lp_amount, RoundDirection::Floor);
+
+            let quote = quote_deposit(&info, DepositSide::Coin, amount);
+            prop_assert_eq!(quote.map(|q| q.pc_amount), expected_pc);
+            prop_assert_eq!(quote.map(|q| q.lp_amount), expected_lp);
+        }
+
+        #[test]
+        fn prop_quote_withdraw_matches_program(
+            total_coin in 1..u64::MAX / 4,
+            total_pc in 1..u64::MAX / 4,
+            lp_amount in 1..u64::MAX / 4,
+            burn_share in 1..10_000u64,
+        ) {
+            let info = pool_info(total_coin, total_pc, lp_amount);
+            let burned = (lp_amount as u128 * burn_share as u128 / 10_000) as u64;
+            let invariant = InvariantPool { token_input: burned, token_total: lp_amount };
+
+            let quote = quote_withdraw(&info, burned).unwrap();
+            prop_assert_eq!(
+                Some(quote.coin_amount),
+                invariant.exchange_pool_to_token(total_coin, RoundDirection::Floor)
+            );
+            prop_assert_eq!(
+                Some(quote.pc_amount),
+                invariant.exchange_pool_to_token(total_pc, RoundDirection::Floor)
+            );
+        }
+
+        #[test]
+        fn prop_deposit_then_withdraw_never_profits(
+            total_coin in 1_000..u64::MAX / 8,
+            total_pc in 1_000..u64::MAX / 8,
+            lp_amount in 1_000..u64::MAX / 8,
+            amount in 1..u32::MAX as u64,
+        ) {
+            let info = pool_info(total_coin, total_pc, lp_amount);
+            let deposit = quote_deposit(&info, DepositSide::Pc, amount).unwrap();
+            let after = pool_info(
+                total_coin + deposit.coin_amount,
+                total_pc + deposit.pc_amount,
+                lp_amount + deposit.lp_amount,
+            );
+            let withdraw = quote_withdraw(&after, deposit.lp_amount).unwrap();
+            prop_assert!(withdraw.coin_amount <= deposit.coin_amount);
+            prop_assert!(withdraw.pc_amount <= deposit.pc_amount);
+        }
+    }
+}
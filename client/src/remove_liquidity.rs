@@ -3,24 +3,37 @@ use log::{debug, error, info};
 use std::error::Error;
 use std::str::FromStr;
 
-use raydium_amm::state::AmmInfo;
-use raydium_library::amm::{self, AmmCommands};
 use solana_sdk::instruction::Instruction;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signer::Signer;
+use spl_associated_token_account::get_associated_token_address;
 
 use crate::cache::{Market, Pool};
-use crate::client_helper::ClientHelper;
-use crate::fetch_pool;
+use crate::client_helper::{ClientHelper, ClientHelperTxResult, WalletKeys};
+use crate::fetch_pool::{self, ExtendedAmmInfo};
+use crate::math::{slippage_to_bps, sub_slippage_bps};
+use crate::pool_keys::PoolKeys;
+use crate::quote;
+use crate::token::{self, UserTokenAccount};
 use crate::*;
 
+// What the withdraw is expected to pay out and the minimums enforced on-chain, amounts with decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemoveLiquidityQuote {
+    pub lp_amount: u64,
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    pub min_coin_amount: u64,
+    pub min_pc_amount: u64,
+}
+
 pub fn build_remove_liquidity_ix(
-    client: &ClientHelper,
-    pool_id: &Pubkey,
-    pool_info: &AmmInfo,
+    client: &impl WalletKeys,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
     input_lp_amount: u64,
     slippage_limit: f64,
-) -> Result<Vec<Instruction>, LiquidityError> {
+) -> Result<(Vec<Instruction>, RemoveLiquidityQuote), LiquidityError> {
     // Basic sanity checks
     if input_lp_amount == 0 {
         return Err(LiquidityError::AmountZero);
@@ -31,51 +44,95 @@ pub fn build_remove_liquidity_ix(
     }
 
     // Build Tx
-    let decimals = pool_info.sys_decimal_value;
+    let decimals = pool_info.amm_info.sys_decimal_value;
     let amount_with_decimals = input_lp_amount
         .checked_mul(decimals)
         .ok_or_else(|| LiquidityError::MultiplicationOverflow)?;
-    let cmd = AmmCommands::Withdraw {
-        pool_id: *pool_id,
-        /// The specified lp token of the user withdraw.
-        /// If none is given, the account will be ATA account.
-        withdraw_token_lp: None,
-        /// The specified token coin of the user will receive.
-        /// If none is given, the account will be ATA account.
-        recipient_token_coin: None,
-        /// The specified token pc of the user will receive.
-        /// If none is given, the account will be ATA account.
-        recipient_token_pc: None,
-        /// The amount of liquidity to burn.
-        input_lp_amount: amount_with_decimals,
-        /// The amount of both tokens to be calculated though `input_lp_amount` may be less than expected due to price fluctuations.
-        /// It's necessary to add an optional parameter to limit the minimum amount of the tokens.
-        slippage_limit: slippage_limit > 0.0,
+
+    // The burned LP pays out its pro-rata share of the reserves (rounded down like the program does),
+    // price fluctuations may lower it so bound both outputs by the caller's slippage
+    let withdraw = quote::quote_withdraw(pool_info, amount_with_decimals)
+        .ok_or(LiquidityError::MultiplicationOverflow)?;
+    let slippage_bps = slippage_to_bps(slippage_limit);
+    let quote = RemoveLiquidityQuote {
+        lp_amount: amount_with_decimals,
+        coin_amount: withdraw.coin_amount,
+        pc_amount: withdraw.pc_amount,
+        min_coin_amount: sub_slippage_bps(withdraw.coin_amount, slippage_bps)
+            .ok_or(LiquidityError::MultiplicationOverflow)?,
+        min_pc_amount: sub_slippage_bps(withdraw.pc_amount, slippage_bps)
+            .ok_or(LiquidityError::MultiplicationOverflow)?,
     };
-    let result =
-        amm::process_amm_commands(cmd, &client.config).map_err(LiquidityError::from)?;
-    result.ok_or(LiquidityError::NoInstructions)
+    debug!("Expected withdraw: {:?}", quote);
+
+    // wrapped SOL outputs land in temporary accounts that are unwrapped afterwards
+    let owner = client.owner();
+    let payer = client.payer();
+    let lp = UserTokenAccount::source(&payer, &owner, &pool_keys.lp_mint, 0);
+    let coin = UserTokenAccount::destination(&payer, &owner, &pool_keys.coin_mint);
+    let pc = UserTokenAccount::destination(&payer, &owner, &pool_keys.pc_mint);
+    let withdraw_ix = raydium_amm::instruction::withdraw(
+        &pool_keys.amm_program,
+        &pool_keys.amm_id,
+        &pool_keys.amm_authority,
+        &pool_keys.amm_open_orders,
+        &pool_keys.amm_target_orders,
+        &pool_keys.lp_mint,
+        &pool_keys.coin_vault,
+        &pool_keys.pc_vault,
+        &pool_keys.market_program,
+        &pool_keys.market_id,
+        &pool_keys.market_coin_vault,
+        &pool_keys.market_pc_vault,
+        &pool_keys.market_vault_signer,
+        &lp.address,
+        &coin.address,
+        &pc.address,
+        &owner,
+        &pool_keys.market_event_queue,
+        &pool_keys.market_bids,
+        &pool_keys.market_asks,
+        None,
+        amount_with_decimals,
+        Some(quote.min_coin_amount),
+        Some(quote.min_pc_amount),
+    )
+    .map_err(|_| LiquidityError::InstructionGenerationFailed)?;
+    let mut instructions = [coin.setup, pc.setup].concat();
+    instructions.push(withdraw_ix);
+    instructions.extend([coin.cleanup, pc.cleanup].concat());
+    Ok((instructions, quote))
 }
 
 // Function to remove liquidity
 pub fn remove_liquidity(
     client: &ClientHelper,
-    pool_id: &Pubkey,
-    pool_info: &AmmInfo,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
     input_lp_amount: u64,
     slippage_limit: f64,
     dryrun: bool,
-) -> Result<(), Box<dyn Error>> {
-    let instructions =
-        build_remove_liquidity_ix(client, pool_id, pool_info, input_lp_amount, slippage_limit)?;
-    client.process_transaction(&instructions, dryrun);
+) -> Result<ClientHelperTxResult, Box<dyn Error>> {
+    let (instructions, quote) = build_remove_liquidity_ix(
+        client,
+        pool_keys,
+        pool_info,
+        input_lp_amount,
+        slippage_limit,
+    )?;
+    info!(
+        "Removing {} LP for at least {} coin and {} pc",
+        quote.lp_amount, quote.min_coin_amount, quote.min_pc_amount
+    );
+    let result = client.process_transaction(&instructions, dryrun)?;
     info!("{}", Colour::Green.paint("Liquidity successfully removed"));
-    Ok(())
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::program_test::Harness;
     #[test]
     fn test_remove_liquidity() {
         // SETUP
@@ -83,6 +140,7 @@ mod tests {
         let market = Market::read_from_cache().unwrap();
         let pool = Pool::read_from_cache(&market.market_id).unwrap();
         let pool_pubkey = Pubkey::from_str(&pool.amm_id).unwrap();
+        let pool_keys = PoolKeys::from_cache(&pool, &market).unwrap();
 
         // BEFORE
         let pool_info = client
@@ -103,14 +161,7 @@ mod tests {
         let slippage = 0.01;
 
         // Add liquidity
-        let result = remove_liquidity(
-            &client,
-            &pool_pubkey,
-            &pool_info.amm_info,
-            amount,
-            slippage,
-            false,
-        );
+        let result = remove_liquidity(&client, &pool_keys, &pool_info, amount, slippage, false);
         assert!(
             result.is_ok(),
             "remove_liquidity failed with error: {:?}",
@@ -118,7 +169,6 @@ mod tests {
         );
 
         // AFTER
-        client.tests_wait_for_confirmation();
         let after_pool_info = client
             .fetch_extended_amm_info(&pool_pubkey)
             .expect("Failed to fetch final pool balances");
@@ -177,4 +227,52 @@ mod tests {
             Colour::Red.paint(after_pool_info.pc_vault_balance.to_string())
         );
     }
+
+    #[test]
+    fn test_offline_build_remove_liquidity_ix() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // BEFORE, the harness user created the pool and holds its LP
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let pre_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+        let pre_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+
+        // EXECUTE
+        let amount = 1; // w/o decimals
+        let (instructions, quote) =
+            build_remove_liquidity_ix(&client, &pool_keys, &pool_info, amount, 0.01).unwrap();
+        let result = harness.process_transaction(&instructions);
+        assert!(
+            result.is_ok(),
+            "remove liquidity transaction failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_pool_info = harness.fetch_extended_amm_info().unwrap();
+        let after_user_lp_amount = harness.fetch_user_balance(&pool_keys.lp_mint).unwrap();
+        let after_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+        let lp_with_decimals = amount * pool_info.amm_info.sys_decimal_value;
+        assert_eq!(
+            pre_user_lp_amount - after_user_lp_amount,
+            lp_with_decimals,
+            "User LP amount should have decreased by exactly the burned amount"
+        );
+        assert_eq!(
+            pool_info.lp_amount - after_pool_info.lp_amount,
+            lp_with_decimals,
+            "Pool LP amount should have decreased by exactly the burned amount"
+        );
+        assert!(
+            after_user_coin_amount - pre_user_coin_amount >= quote.min_coin_amount,
+            "User coin amount should have increased by at least the quoted minimum"
+        );
+        assert!(
+            quote.min_coin_amount <= quote.coin_amount && quote.min_pc_amount <= quote.pc_amount,
+            "Quoted minimums should not exceed the expected amounts"
+        );
+    }
 }
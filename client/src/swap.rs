@@ -0,0 +1,314 @@
+use ansi_term::Colour;
+use log::{debug, info};
+use std::error::Error;
+use std::str::FromStr;
+
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signer::Signer;
+
+use crate::cache::{Market, Pool};
+use crate::client_helper::{ClientHelper, ClientHelperTxResult, WalletKeys};
+use crate::fetch_pool::ExtendedAmmInfo;
+use crate::math::{add_slippage_bps, slippage_to_bps, sub_slippage_bps};
+use crate::pool_keys::PoolKeys;
+use crate::quote::{self, SwapDirection, SwapQuote};
+use crate::token::UserTokenAccount;
+use crate::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapMode {
+    // spend exactly the amount, receive at least the quoted output minus slippage
+    ExactIn,
+    // receive exactly the amount, spend at most the quoted input plus slippage
+    ExactOut,
+}
+
+// Quoted swap and the bounds enforced on-chain, amounts with decimals
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapBounds {
+    pub quote: SwapQuote,
+    pub maximum_amount_in: u64,
+    pub minimum_amount_out: u64,
+}
+
+// Function to build the transaction instructions for a swap, `amount` is raw (with decimals)
+pub fn build_swap_ix(
+    client: &impl WalletKeys,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
+    input_mint: &Pubkey,
+    amount: u64,
+    mode: SwapMode,
+    slippage: f64,
+) -> Result<(Vec<Instruction>, SwapBounds), LiquidityError> {
+    // Basic sanity checks, the program does more checks for us already
+    if amount == 0 {
+        return Err(LiquidityError::AmountZero);
+    }
+    // Check that slippage is between 0 and 1
+    if !(0.0..=1.0).contains(&slippage) {
+        return Err(LiquidityError::SlippageOutOfRange);
+    }
+    let (direction, output_mint) = if *input_mint == pool_keys.coin_mint {
+        (SwapDirection::CoinToPc, pool_keys.pc_mint)
+    } else if *input_mint == pool_keys.pc_mint {
+        (SwapDirection::PcToCoin, pool_keys.coin_mint)
+    } else {
+        return Err(LiquidityError::MintNotInPool(*input_mint));
+    };
+
+    let slippage_bps = slippage_to_bps(slippage);
+    let bounds = match mode {
+        SwapMode::ExactIn => {
+            let quote = quote::quote_swap_base_in(pool_info, direction, amount)
+                .ok_or(LiquidityError::MultiplicationOverflow)?;
+            SwapBounds {
+                quote,
+                maximum_amount_in: amount,
+                minimum_amount_out: sub_slippage_bps(quote.amount_out, slippage_bps)
+                    .ok_or(LiquidityError::MultiplicationOverflow)?,
+            }
+        }
+        SwapMode::ExactOut => {
+            let quote = quote::quote_swap_base_out(pool_info, direction, amount)
+                .ok_or(LiquidityError::MultiplicationOverflow)?;
+            SwapBounds {
+                quote,
+                maximum_amount_in: add_slippage_bps(quote.amount_in, slippage_bps)
+                    .ok_or(LiquidityError::MultiplicationOverflow)?,
+                minimum_amount_out: amount,
+            }
+        }
+    };
+    debug!("Expected swap: {:?}", bounds);
+
+    // wrapped SOL input is funded with the most the swap may spend, the rest is unwrapped afterwards
+    let owner = client.owner();
+    let payer = client.payer();
+    let source = UserTokenAccount::source(&payer, &owner, input_mint, bounds.maximum_amount_in);
+    let destination = UserTokenAccount::destination(&payer, &owner, &output_mint);
+
+    let swap_ix = match mode {
+        SwapMode::ExactIn => raydium_amm::instruction::swap_base_in(
+            &pool_keys.amm_program,
+            &pool_keys.amm_id,
+            &pool_keys.amm_authority,
+            &pool_keys.amm_open_orders,
+            &pool_keys.amm_target_orders,
+            &pool_keys.coin_vault,
+            &pool_keys.pc_vault,
+            &pool_keys.market_program,
+            &pool_keys.market_id,
+            &pool_keys.market_bids,
+            &pool_keys.market_asks,
+            &pool_keys.market_event_queue,
+            &pool_keys.market_coin_vault,
+            &pool_keys.market_pc_vault,
+            &pool_keys.market_vault_signer,
+            &source.address,
+            &destination.address,
+            &owner,
+            amount,
+            bounds.minimum_amount_out,
+        ),
+        SwapMode::ExactOut => raydium_amm::instruction::swap_base_out(
+            &pool_keys.amm_program,
+            &pool_keys.amm_id,
+            &pool_keys.amm_authority,
+            &pool_keys.amm_open_orders,
+            &pool_keys.amm_target_orders,
+            &pool_keys.coin_vault,
+            &pool_keys.pc_vault,
+            &pool_keys.market_program,
+            &pool_keys.market_id,
+            &pool_keys.market_bids,
+            &pool_keys.market_asks,
+            &pool_keys.market_event_queue,
+            &pool_keys.market_coin_vault,
+            &pool_keys.market_pc_vault,
+            &pool_keys.market_vault_signer,
+            &source.address,
+            &destination.address,
+            &owner,
+            bounds.maximum_amount_in,
+            amount,
+        ),
+    }
+    .map_err(|_| LiquidityError::InstructionGenerationFailed)?;
+
+    let mut instructions = source.setup;
+    instructions.extend(destination.setup);
+    instructions.push(swap_ix);
+    instructions.extend(source.cleanup);
+    instructions.extend(destination.cleanup);
+    Ok((instructions, bounds))
+}
+
+// Function to swap
+#[allow(clippy::too_many_arguments)]
+pub fn swap(
+    client: &ClientHelper,
+    pool_keys: &PoolKeys,
+    pool_info: &ExtendedAmmInfo,
+    input_mint: &Pubkey,
+    amount: u64,
+    mode: SwapMode,
+    slippage: f64,
+    dryrun: bool,
+) -> Result<ClientHelperTxResult, Box<dyn Error>> {
+    let (instructions, bounds) = build_swap_ix(
+        client, pool_keys, pool_info, input_mint, amount, mode, slippage,
+    )?;
+    info!(
+        "Swapping {} for {} (max in {}, min out {})",
+        bounds.quote.amount_in,
+        bounds.quote.amount_out,
+        bounds.maximum_amount_in,
+        bounds.minimum_amount_out
+    );
+    let result = client.process_transaction(&instructions, dryrun)?;
+    info!("{}", Colour::Green.paint("Swap successfully executed"));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::program_test::Harness;
+
+    #[test]
+    fn test_swap() {
+        // SETUP
+        let client = ClientHelper::default();
+        let market = Market::read_from_cache().unwrap();
+        let pool = Pool::read_from_cache(&market.market_id).unwrap();
+        let pool_pubkey = Pubkey::from_str(&pool.amm_id).unwrap();
+        let pool_keys = PoolKeys::from_cache(&pool, &market).unwrap();
+        let user = client.user_keypair.pubkey();
+
+        // BEFORE
+        let pool_info = client
+            .fetch_extended_amm_info(&pool_pubkey)
+            .expect("Failed to fetch initial pool balances");
+        let pre_user_pc_amount = client
+            .derive_ata_and_fetch_balance(&user, &pool_keys.pc_mint)
+            .unwrap();
+
+        // EXECUTE
+        let amount = pool_info.amm_info.sys_decimal_value; // 1 coin with decimals
+        let result = swap(
+            &client,
+            &pool_keys,
+            &pool_info,
+            &pool_keys.coin_mint,
+            amount,
+            SwapMode::ExactIn,
+            0.01,
+            false,
+        );
+        assert!(
+            result.is_ok(),
+            "swap failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_pc_amount = client
+            .derive_ata_and_fetch_balance(&user, &pool_keys.pc_mint)
+            .unwrap();
+        info!(
+            "User PC amount {} -> {}",
+            Colour::Red.paint(pre_user_pc_amount.to_string()),
+            Colour::Green.paint(after_user_pc_amount.to_string())
+        );
+        assert!(
+            after_user_pc_amount > pre_user_pc_amount,
+            "User PC amount should have increased"
+        );
+    }
+
+    #[test]
+    fn test_offline_build_swap_ix() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let pre_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+        let pre_user_pc_amount = harness.fetch_user_balance(&pool_keys.pc_mint).unwrap();
+
+        // EXECUTE
+        let amount = pool_info.amm_info.sys_decimal_value;
+        let (instructions, bounds) = build_swap_ix(
+            &client,
+            &pool_keys,
+            &pool_info,
+            &pool_keys.coin_mint,
+            amount,
+            SwapMode::ExactIn,
+            0.01,
+        )
+        .unwrap();
+        let result = client.process_transaction(&instructions, false);
+        assert!(
+            result.is_ok(),
+            "swap transaction failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+        let after_user_pc_amount = harness.fetch_user_balance(&pool_keys.pc_mint).unwrap();
+        assert_eq!(pre_user_coin_amount - after_user_coin_amount, amount);
+        assert_eq!(
+            after_user_pc_amount - pre_user_pc_amount,
+            bounds.quote.amount_out,
+            "User should have received exactly the quoted output"
+        );
+    }
+
+    #[test]
+    fn test_offline_build_swap_exact_out_ix() {
+        // SETUP
+        let harness = Harness::start().unwrap();
+        let client = harness.client_helper();
+        let pool_keys = harness.pool_keys.clone();
+
+        // BEFORE
+        let pool_info = harness.fetch_extended_amm_info().unwrap();
+        let pre_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+        let pre_user_pc_amount = harness.fetch_user_balance(&pool_keys.pc_mint).unwrap();
+
+        // EXECUTE
+        let amount = pool_info.amm_info.sys_decimal_value;
+        let (instructions, bounds) = build_swap_ix(
+            &client,
+            &pool_keys,
+            &pool_info,
+            &pool_keys.pc_mint,
+            amount,
+            SwapMode::ExactOut,
+            0.01,
+        )
+        .unwrap();
+        let result = client.process_transaction(&instructions, false);
+        assert!(
+            result.is_ok(),
+            "swap transaction failed with error: {:?}",
+            result.unwrap_err()
+        );
+
+        // EXPECT
+        let after_user_coin_amount = harness.fetch_user_balance(&pool_keys.coin_mint).unwrap();
+        let after_user_pc_amount = harness.fetch_user_balance(&pool_keys.pc_mint).unwrap();
+        assert_eq!(after_user_coin_amount - pre_user_coin_amount, amount);
+        assert_eq!(
+            pre_user_pc_amount - after_user_pc_amount,
+            bounds.quote.amount_in,
+            "User should have spent exactly the quoted input"
+        );
+    }
+}
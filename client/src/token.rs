@@ -1,6 +1,8 @@
 use solana_sdk::{
-    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, system_instruction,
+    instruction::Instruction, program_pack::Pack, pubkey::Pubkey, rent::Rent, signature::Keypair,
+    signer::Signer, system_instruction,
 };
+use spl_associated_token_account::get_associated_token_address;
 
 pub fn create_ata_token_or_not(
     funding: &Pubkey,
@@ -73,8 +75,8 @@ pub fn mint_to(
     vec![spl_token_2022::instruction::mint_to(
         token_program.unwrap_or(&spl_token::id()),
         mint,
-        &to_token,
-        &mint_authority,
+        to_token,
+        mint_authority,
         &[],
         amount,
     )
@@ -92,7 +94,7 @@ pub fn transfer_to(
         token_program.unwrap_or(&spl_token::id()),
         from,
         to,
-        &from_authority,
+        from_authority,
         &[],
         amount,
     )
@@ -109,7 +111,7 @@ pub fn close_spl_account(
         token_program.unwrap_or(&spl_token::id()),
         close_account,
         destination,
-        &close_authority,
+        close_authority,
         &[],
     )
     .unwrap()]
@@ -121,3 +123,201 @@ pub fn wrap_sol_instructions(from: &Pubkey, to: &Pubkey, amount: u64) -> Vec<Ins
         spl_token::instruction::sync_native(&spl_token::id(), to).unwrap(),
     ]
 }
+
+// Token account the user operates with for `mint`, wrapped SOL goes through a temporary account
+// derived from the owner so the whole wrap / operate / unwrap cycle fits in one transaction
+pub struct UserTokenAccount {
+    pub address: Pubkey,
+    // instructions to run before the operation
+    pub setup: Vec<Instruction>,
+    // instructions to run after the operation
+    pub cleanup: Vec<Instruction>,
+}
+
+impl UserTokenAccount {
+    // Account the operation spends from, `native_amount` lamports are wrapped when `mint` is the native mint
+    pub fn source(funding: &Pubkey, owner: &Pubkey, mint: &Pubkey, native_amount: u64) -> Self {
+        if *mint == spl_token::native_mint::id() {
+            return Self::temporary_wsol(funding, owner, native_amount);
+        }
+        Self {
+            address: get_associated_token_address(owner, mint),
+            setup: Vec::new(),
+            cleanup: Vec::new(),
+        }
+    }
+
+    // Account the operation pays into, created if missing
+    pub fn destination(funding: &Pubkey, owner: &Pubkey, mint: &Pubkey) -> Self {
+        if *mint == spl_token::native_mint::id() {
+            return Self::temporary_wsol(funding, owner, 0);
+        }
+        Self {
+            address: get_associated_token_address(owner, mint),
+            setup: create_ata_token_or_not(funding, mint, owner, None),
+            cleanup: Vec::new(),
+        }
+    }
+
+    // Rent is fronted by `funding`, the owner only wraps `amount`. Closing hands everything to the
+    // owner, so a sponsor gets its rent back with a transfer the owner signs
+    pub fn temporary_wsol(funding: &Pubkey, owner: &Pubkey, amount: u64) -> Self {
+        let seed = Keypair::new().pubkey().to_string()[..32].to_string();
+        let address = Pubkey::create_with_seed(owner, &seed, &spl_token::id()).unwrap();
+        let space = spl_token::state::Account::LEN;
+        let rent = Rent::default().minimum_balance(space);
+        let mut setup = vec![
+            system_instruction::create_account_with_seed(
+                funding,
+                &address,
+                owner,
+                &seed,
+                rent,
+                space as u64,
+                &spl_token::id(),
+            ),
+            spl_token::instruction::initialize_account3(
+                &spl_token::id(),
+                &address,
+                &spl_token::native_mint::id(),
+                owner,
+            )
+            .unwrap(),
+        ];
+        if amount > 0 {
+            setup.extend(wrap_sol_instructions(owner, &address, amount));
+        }
+        let mut cleanup = close_spl_account(&address, owner, owner, None);
+        if funding != owner {
+            cleanup.push(system_instruction::transfer(owner, funding, rent));
+        }
+        Self {
+            address,
+            setup,
+            cleanup,
+        }
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        !self.cleanup.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{BanksBackend, TransactionBackend};
+    use solana_program_test::ProgramTest;
+    use solana_sdk::account::Account;
+    use solana_sdk::native_token::LAMPORTS_PER_SOL;
+    use solana_sdk::system_program;
+    use solana_sdk::transaction::Transaction;
+    use tokio::runtime::Runtime;
+
+    #[test]
+    fn test_user_token_account_wraps_native_mint() {
+        let owner = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+
+        let source = UserTokenAccount::source(&owner, &owner, &mint, 42);
+        assert_eq!(source.address, get_associated_token_address(&owner, &mint));
+        assert!(source.setup.is_empty() && !source.is_temporary());
+        let destination = UserTokenAccount::destination(&owner, &owner, &mint);
+        assert_eq!(destination.setup.len(), 1);
+
+        // create, initialize, transfer and sync, then close
+        let native_mint = spl_token::native_mint::id();
+        let wsol = UserTokenAccount::source(&owner, &owner, &native_mint, 42);
+        assert_ne!(
+            wsol.address,
+            get_associated_token_address(&owner, &native_mint)
+        );
+        assert_eq!(wsol.setup.len(), 4);
+        assert_eq!(wsol.cleanup.len(), 1);
+        assert_eq!(
+            UserTokenAccount::destination(&owner, &owner, &native_mint)
+                .setup
+                .len(),
+            2
+        );
+
+        // a sponsor funds the rent and is refunded after the close
+        let sponsor = Pubkey::new_unique();
+        let sponsored = UserTokenAccount::source(&sponsor, &owner, &native_mint, 42);
+        assert_eq!(sponsored.setup[0].accounts[0].pubkey, sponsor);
+        assert_eq!(sponsored.cleanup.len(), 2);
+    }
+
+    #[test]
+    fn test_sponsored_wsol_only_spends_the_wrapped_amount() {
+        // SETUP
+        let owner = Keypair::new();
+        let recipient = Keypair::new();
+        let sponsor = Keypair::new();
+        let mut program_test = ProgramTest::default();
+        for wallet in [&owner, &recipient, &sponsor] {
+            program_test.add_account(
+                wallet.pubkey(),
+                Account::new(LAMPORTS_PER_SOL, 0, &system_program::id()),
+            );
+        }
+        let runtime = Runtime::new().unwrap();
+        let context = runtime.block_on(program_test.start_with_context());
+        let backend = BanksBackend::new(runtime, context.banks_client.clone());
+        let lamports = |address: &Pubkey| backend.get_account(address).unwrap().unwrap().lamports;
+
+        // BEFORE
+        let pre_owner_lamports = lamports(&owner.pubkey());
+        let pre_recipient_lamports = lamports(&recipient.pubkey());
+        let pre_sponsor_lamports = lamports(&sponsor.pubkey());
+
+        // EXECUTE
+        // wrap more than is spent, the rest is unwrapped back to the owner
+        let (wrapped, spent) = (1_000_000, 400_000);
+        let native_mint = spl_token::native_mint::id();
+        let source =
+            UserTokenAccount::source(&sponsor.pubkey(), &owner.pubkey(), &native_mint, wrapped);
+        let destination =
+            UserTokenAccount::destination(&sponsor.pubkey(), &recipient.pubkey(), &native_mint);
+        let transfer = spl_token::instruction::transfer(
+            &spl_token::id(),
+            &source.address,
+            &destination.address,
+            &owner.pubkey(),
+            &[],
+            spent,
+        )
+        .unwrap();
+        let instructions = [
+            source.setup,
+            destination.setup,
+            vec![transfer],
+            source.cleanup,
+            destination.cleanup,
+        ]
+        .concat();
+        let txn = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&sponsor.pubkey()),
+            &[&sponsor, &owner, &recipient],
+            backend.get_latest_blockhash().unwrap(),
+        );
+        backend.send_transaction(&txn).unwrap();
+
+        // EXPECT
+        assert_eq!(
+            pre_owner_lamports - lamports(&owner.pubkey()),
+            spent,
+            "The owner should only pay the wrapped amount it spent"
+        );
+        assert_eq!(
+            lamports(&recipient.pubkey()) - pre_recipient_lamports,
+            spent
+        );
+        let rent = Rent::default().minimum_balance(spl_token::state::Account::LEN);
+        assert!(
+            pre_sponsor_lamports - lamports(&sponsor.pubkey()) < rent,
+            "The sponsor should get the rent back and only pay the fees"
+        );
+    }
+}
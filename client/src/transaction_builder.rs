@@ -0,0 +1,170 @@
+// The sign, simulate, send and confirm pipeline shared by ClientHelper and AsyncClientHelper.
+// The builder does no IO: each client fetches what a step needs and hands the result back, so
+// both clients budget, sign and report errors the same way.
+use log::{debug, error};
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Signature, Signer};
+use solana_sdk::signer::SignerError;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+use crate::client_helper::ClientHelperError;
+use crate::compute_budget::{fee_accounts, ComputeBudgetConfig, MAX_COMPUTE_UNIT_LIMIT};
+use crate::confirmation::ConfirmationError;
+use crate::program_error::PoolProgramError;
+
+pub(crate) struct TransactionBuilder<'a> {
+    instructions: &'a [Instruction],
+    payer: Pubkey,
+    compute_budget: &'a ComputeBudgetConfig,
+    // decodes the AMM errors in the simulation logs
+    amm_program: Pubkey,
+    unit_limit: Option<u32>,
+    unit_price: Option<u64>,
+}
+
+impl<'a> TransactionBuilder<'a> {
+    pub fn new(
+        instructions: &'a [Instruction],
+        payer: Pubkey,
+        compute_budget: &'a ComputeBudgetConfig,
+        amm_program: Pubkey,
+    ) -> Self {
+        // simulate with the maximum limit so the unit count is not capped by the default
+        let unit_limit = compute_budget
+            .simulate_unit_limit
+            .then_some(MAX_COMPUTE_UNIT_LIMIT);
+        Self {
+            instructions,
+            payer,
+            compute_budget,
+            amm_program,
+            unit_limit,
+            unit_price: None,
+        }
+    }
+
+    // Accounts to fetch the recent prioritization fees of, none when the strategy does not use them
+    pub fn fee_accounts(&self) -> Option<Vec<Pubkey>> {
+        let strategy = self.compute_budget.priority_fee.as_ref()?;
+        strategy
+            .needs_recent_fees()
+            .then(|| fee_accounts(&Message::new(self.instructions, Some(&self.payer))))
+    }
+
+    pub fn with_recent_fees(mut self, recent_fees: &[u64]) -> Self {
+        self.unit_price = self
+            .compute_budget
+            .priority_fee
+            .as_ref()
+            .map(|strategy| strategy.price(recent_fees));
+        self
+    }
+
+    pub fn message(&self) -> Message {
+        let instructions =
+            self.compute_budget
+                .apply(self.instructions, self.unit_limit, self.unit_price);
+        Message::new(&instructions, Some(&self.payer))
+    }
+
+    // Fails on a simulation error, otherwise sizes the unit limit from the simulated units.
+    // Returns whether the message changed and has to be signed again.
+    pub fn simulated(
+        &mut self,
+        sim_result: &RpcSimulateTransactionResult,
+    ) -> Result<bool, ClientHelperError> {
+        debug!("\nSimulation Result: {:#?}\n\n", sim_result);
+        if let Some(err) = sim_result.err.clone() {
+            let error = simulation_error(err, sim_result.logs.clone(), &self.amm_program);
+            if let Some(program_error) = error.program_error() {
+                error!("\nSimulation Error: {}\n\n", program_error);
+            }
+            return Err(error);
+        }
+        if !self.compute_budget.simulate_unit_limit {
+            return Ok(false);
+        }
+        self.unit_limit = sim_result
+            .units_consumed
+            .map(|units| self.compute_budget.unit_limit(units));
+        debug!(
+            "\nCompute units: {:?}, price: {:?}\n\n",
+            self.unit_limit, self.unit_price
+        );
+        Ok(true)
+    }
+}
+
+// Signs with the candidates the message requires, see `required_signers`
+pub(crate) fn sign_message<'a>(
+    message: Message,
+    candidates: impl IntoIterator<Item = &'a dyn Signer>,
+    blockhash: Hash,
+) -> Result<Transaction, SignerError> {
+    let mut txn = Transaction::new_unsigned(message);
+    let signers = required_signers(&txn.message, candidates);
+    txn.try_sign(&signers, blockhash)?;
+    Ok(txn)
+}
+
+// The payer and the owner are only distinct with a sponsor, and a sponsored transaction
+// does not always need the owner (e.g. creating a market), so only the keys the message
+// requires sign. A missing one surfaces as SignerError::NotEnoughSigners.
+pub(crate) fn required_signers<'a>(
+    message: &Message,
+    candidates: impl IntoIterator<Item = &'a dyn Signer>,
+) -> Vec<&'a dyn Signer> {
+    let required = &message.account_keys[..message.header.num_required_signatures as usize];
+    let mut signers: Vec<&dyn Signer> = Vec::new();
+    for signer in candidates {
+        let pubkey = signer.pubkey();
+        if required.contains(&pubkey) && !signers.iter().any(|s| s.pubkey() == pubkey) {
+            signers.push(signer);
+        }
+    }
+    signers
+}
+
+pub(crate) fn simulation_error(
+    err: TransactionError,
+    logs: Option<Vec<String>>,
+    amm_program: &Pubkey,
+) -> ClientHelperError {
+    if err == TransactionError::BlockhashNotFound {
+        return ClientHelperError::BlockhashExpired { signature: None };
+    }
+    let logs = logs.unwrap_or_default();
+    let program_error = PoolProgramError::from_logs(&logs, amm_program);
+    ClientHelperError::Simulation {
+        err,
+        logs,
+        program_error,
+    }
+}
+
+// `blockhash_valid` is only looked up on a timeout, an expired blockhash means the transaction
+// can no longer land
+pub(crate) fn confirmation_error(
+    signature: Signature,
+    err: ConfirmationError,
+    blockhash_valid: Option<bool>,
+) -> ClientHelperError {
+    match err {
+        ConfirmationError::Timeout { .. } if blockhash_valid == Some(false) => {
+            ClientHelperError::BlockhashExpired {
+                signature: Some(signature),
+            }
+        }
+        ConfirmationError::Timeout { elapsed, .. } => {
+            ClientHelperError::ConfirmationTimeout { signature, elapsed }
+        }
+        ConfirmationError::TransactionFailed { err, .. } => {
+            ClientHelperError::TransactionFailed { signature, err }
+        }
+        other => ClientHelperError::Backend(other.into()),
+    }
+}
@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::pubkey;
+
+// Raydium AMM v4 deployments the puppet is allowed to CPI into
+pub const AMM_V4_PROGRAM_IDS: [Pubkey; 2] = [
+    pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8"), // mainnet-beta
+    pubkey!("HWy1jotHpo6UqeQxx49dpYYdQB8wj9Qk9MdxwjLvDHB8"), // devnet
+];
+
+// OpenBook deployments backing the AMM v4 pools
+pub const OPENBOOK_PROGRAM_IDS: [Pubkey; 2] = [
+    pubkey!("srmqPvymJeFKQ4zGQed1GFppgkRHL9kaELCbyksJtPX"), // mainnet-beta
+    pubkey!("EoTcMgcDRTJVZDMZWBoU6rhYHZfkNTVEAfz3uUJRcYGj"), // devnet
+];
+
+// Seeds used by the AMM v4 program, every pool account is derived as
+// [amm_program, market, <seed>] except the authority which is [AMM_AUTHORITY_SEED]
+pub const AMM_AUTHORITY_SEED: &[u8] = b"amm authority";
+pub const AMM_ASSOCIATED_SEED: &[u8] = b"amm_associated_seed";
+pub const TARGET_ASSOCIATED_SEED: &[u8] = b"target_associated_seed";
+pub const OPEN_ORDER_ASSOCIATED_SEED: &[u8] = b"open_order_associated_seed";
+pub const COIN_VAULT_ASSOCIATED_SEED: &[u8] = b"coin_vault_associated_seed";
+pub const PC_VAULT_ASSOCIATED_SEED: &[u8] = b"pc_vault_associated_seed";
+pub const LP_MINT_ASSOCIATED_SEED: &[u8] = b"lp_mint_associated_seed";
+// Legacy accounts the program no longer reads, still derived for the pool keys
+pub const WITHDRAW_ASSOCIATED_SEED: &[u8] = b"withdraw_associated_seed";
+pub const TEMP_LP_TOKEN_ASSOCIATED_SEED: &[u8] = b"temp_lp_token_associated_seed";
+// Global AMM config, [AMM_CONFIG_SEED]
+pub const AMM_CONFIG_SEED: &[u8] = b"amm_config_account_seed";
+
+// Seed of the pool level vault, [VAULT_SEED, amm]
+pub const VAULT_SEED: &[u8] = b"vault";
+// Seed of a depositor's position in a vault, [VAULT_POSITION_SEED, vault, owner]
+pub const VAULT_POSITION_SEED: &[u8] = b"vault_position";
+
+// Seed of the singleton admin config
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const MAX_ALLOWED_POOLS: usize = 32;
+
+pub fn is_amm_program(key: &Pubkey) -> bool {
+    AMM_V4_PROGRAM_IDS.contains(key)
+}
+
+pub fn is_openbook_program(key: &Pubkey) -> bool {
+    OPENBOOK_PROGRAM_IDS.contains(key)
+}
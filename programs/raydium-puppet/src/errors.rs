@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+#[error_code]
+pub enum PuppetError {
+    #[msg("Amount must be greater than zero")]
+    AmountZero,
+
+    #[msg("Program is not a supported Raydium AMM v4 deployment")]
+    InvalidAmmProgram,
+
+    #[msg("Program is not a supported OpenBook deployment")]
+    InvalidMarketProgram,
+
+    #[msg("Market is not owned by the market program")]
+    InvalidMarket,
+
+    #[msg("LP delta is outside of the requested bounds")]
+    LpDeltaOutOfBounds,
+
+    #[msg("Coin delta is outside of the requested bounds")]
+    CoinDeltaOutOfBounds,
+
+    #[msg("Pc delta is outside of the requested bounds")]
+    PcDeltaOutOfBounds,
+
+    #[msg("Token balance moved in the wrong direction")]
+    UnexpectedBalanceChange,
+
+    #[msg("Swap accounts do not match the pool mints")]
+    InvalidSwapMint,
+
+    #[msg("Received amount is below the requested minimum")]
+    SlippageExceeded,
+
+    #[msg("Vault does not hold enough of the requested token")]
+    InsufficientVaultBalance,
+
+    #[msg("Vault does not hold enough LP shares")]
+    InsufficientShares,
+
+    #[msg("Math overflow")]
+    MathOverflow,
+
+    #[msg("Signer is not the config authority")]
+    Unauthorized,
+
+    #[msg("Program is paused")]
+    Paused,
+
+    #[msg("Pool is not in the allowlist")]
+    PoolNotAllowed,
+
+    #[msg("Pool is already in the allowlist")]
+    PoolAlreadyAllowed,
+
+    #[msg("Pool allowlist is full")]
+    AllowlistFull,
+}
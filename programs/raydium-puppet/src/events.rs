@@ -0,0 +1,40 @@
+use anchor_lang::prelude::*;
+
+/// Emitted after every deposit CPI, vault balances are the pool vaults once the CPI landed
+#[event]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositEvent {
+    pub amm: Pubkey,
+    pub user: Pubkey,
+    pub lp_minted: u64,
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    pub coin_vault_balance: u64,
+    pub pc_vault_balance: u64,
+}
+
+/// Emitted after every withdraw CPI
+#[event]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawEvent {
+    pub amm: Pubkey,
+    pub user: Pubkey,
+    pub lp_burned: u64,
+    pub coin_amount: u64,
+    pub pc_amount: u64,
+    pub coin_vault_balance: u64,
+    pub pc_vault_balance: u64,
+}
+
+/// Emitted after every swap CPI, `input_mint` tells which side of the pool was sold
+#[event]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwapEvent {
+    pub amm: Pubkey,
+    pub user: Pubkey,
+    pub input_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub coin_vault_balance: u64,
+    pub pc_vault_balance: u64,
+}
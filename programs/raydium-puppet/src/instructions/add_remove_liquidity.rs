@@ -0,0 +1,133 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::PuppetError;
+use crate::events::{DepositEvent, WithdrawEvent};
+use crate::instructions::{PuppetDeposit, PuppetWithdraw};
+use crate::utils::{decrease, increase};
+
+/// Bounds checked on-chain once both legs have landed, any breach reverts the whole instruction
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug)]
+pub struct AddRemoveBounds {
+    /// Maximum coin spent by the deposit leg
+    pub max_coin_in: u64,
+    /// Maximum pc spent by the deposit leg
+    pub max_pc_in: u64,
+    /// Minimum LP tokens minted by the deposit leg
+    pub min_lp_minted: u64,
+    /// Minimum coin received by the withdraw leg
+    pub min_coin_out: u64,
+    /// Minimum pc received by the withdraw leg
+    pub min_pc_out: u64,
+}
+
+#[derive(Accounts)]
+pub struct PuppetAddRemoveLiquidity<'info> {
+    pub deposit: PuppetDeposit<'info>,
+    // can be the same pool as the deposit leg, the balances are re-read between the two CPIs
+    pub withdraw: PuppetWithdraw<'info>,
+}
+
+pub fn add_remove_liquidity(
+    ctx: Context<PuppetAddRemoveLiquidity>,
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+    withdraw_lp_amount: u64,
+    bounds: AddRemoveBounds,
+) -> Result<()> {
+    require!(
+        max_coin_amount > 0 && max_pc_amount > 0 && withdraw_lp_amount > 0,
+        PuppetError::AmountZero
+    );
+
+    // DEPOSIT LEG
+    let deposit = &mut ctx.accounts.deposit;
+    let pre_coin = deposit.user_token_coin.amount;
+    let pre_pc = deposit.user_token_pc.amount;
+    let pre_lp = deposit.user_token_lp.amount;
+    amm_anchor::deposit(
+        (&mut *deposit).into(),
+        max_coin_amount,
+        max_pc_amount,
+        base_side,
+    )?;
+    deposit.user_token_coin.reload()?;
+    deposit.user_token_pc.reload()?;
+    deposit.user_token_lp.reload()?;
+    deposit.pool.reload_vaults()?;
+
+    let coin_in = decrease(pre_coin, deposit.user_token_coin.amount)?;
+    let pc_in = decrease(pre_pc, deposit.user_token_pc.amount)?;
+    let lp_minted = increase(pre_lp, deposit.user_token_lp.amount)?;
+    msg!(
+        "deposit: coin_in={} pc_in={} lp_minted={}",
+        coin_in,
+        pc_in,
+        lp_minted
+    );
+    require!(
+        coin_in <= max_coin_amount && coin_in <= bounds.max_coin_in,
+        PuppetError::CoinDeltaOutOfBounds
+    );
+    require!(
+        pc_in <= max_pc_amount && pc_in <= bounds.max_pc_in,
+        PuppetError::PcDeltaOutOfBounds
+    );
+    require!(
+        lp_minted >= bounds.min_lp_minted,
+        PuppetError::LpDeltaOutOfBounds
+    );
+    emit!(DepositEvent {
+        amm: deposit.pool.amm.key(),
+        user: deposit.user_owner.key(),
+        lp_minted,
+        coin_amount: coin_in,
+        pc_amount: pc_in,
+        coin_vault_balance: deposit.pool.amm_coin_vault.amount,
+        pc_vault_balance: deposit.pool.amm_pc_vault.amount,
+    });
+
+    // WITHDRAW LEG
+    let withdraw = &mut ctx.accounts.withdraw;
+    withdraw.user_token_coin.reload()?;
+    withdraw.user_token_pc.reload()?;
+    withdraw.user_token_lp.reload()?;
+    let pre_coin = withdraw.user_token_coin.amount;
+    let pre_pc = withdraw.user_token_pc.amount;
+    let pre_lp = withdraw.user_token_lp.amount;
+    amm_anchor::withdraw((&mut *withdraw).into(), withdraw_lp_amount)?;
+    withdraw.user_token_coin.reload()?;
+    withdraw.user_token_pc.reload()?;
+    withdraw.user_token_lp.reload()?;
+    withdraw.pool.reload_vaults()?;
+
+    let coin_out = increase(pre_coin, withdraw.user_token_coin.amount)?;
+    let pc_out = increase(pre_pc, withdraw.user_token_pc.amount)?;
+    let lp_burned = decrease(pre_lp, withdraw.user_token_lp.amount)?;
+    msg!(
+        "withdraw: coin_out={} pc_out={} lp_burned={}",
+        coin_out,
+        pc_out,
+        lp_burned
+    );
+    require!(
+        lp_burned == withdraw_lp_amount,
+        PuppetError::LpDeltaOutOfBounds
+    );
+    require!(
+        coin_out >= bounds.min_coin_out,
+        PuppetError::CoinDeltaOutOfBounds
+    );
+    require!(pc_out >= bounds.min_pc_out, PuppetError::PcDeltaOutOfBounds);
+    emit!(WithdrawEvent {
+        amm: withdraw.pool.amm.key(),
+        user: withdraw.user_owner.key(),
+        lp_burned,
+        coin_amount: coin_out,
+        pc_amount: pc_out,
+        coin_vault_balance: withdraw.pool.amm_coin_vault.amount,
+        pc_vault_balance: withdraw.pool.amm_pc_vault.amount,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::*;
+use crate::errors::PuppetError;
+use crate::program::RaydiumPuppet;
+use crate::state::Config;
+
+// Only the upgrade authority can create the singleton, otherwise the first caller after a
+// deploy would own the pause switch and the allowlist
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + Config::INIT_SPACE,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, RaydiumPuppet>,
+    #[account(
+        constraint = program_data.upgrade_authority_address == Some(authority.key())
+            @ PuppetError::Unauthorized,
+    )]
+    pub program_data: Account<'info, ProgramData>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        has_one = authority @ PuppetError::Unauthorized,
+    )]
+    pub config: Account<'info, Config>,
+    pub authority: Signer<'info>,
+}
+
+pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    config.authority = ctx.accounts.authority.key();
+    config.paused = false;
+    config.bump = ctx.bumps.config;
+    config.allowed_pools = Vec::new();
+    Ok(())
+}
+
+pub fn transfer_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+    msg!(
+        "authority: {} -> {}",
+        ctx.accounts.config.authority,
+        new_authority
+    );
+    ctx.accounts.config.authority = new_authority;
+    Ok(())
+}
+
+pub fn set_paused(ctx: Context<UpdateConfig>, paused: bool) -> Result<()> {
+    msg!("paused: {}", paused);
+    ctx.accounts.config.paused = paused;
+    Ok(())
+}
+
+pub fn allow_pool(ctx: Context<UpdateConfig>, amm: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    require!(
+        !config.is_pool_allowed(&amm),
+        PuppetError::PoolAlreadyAllowed
+    );
+    require!(
+        config.allowed_pools.len() < MAX_ALLOWED_POOLS,
+        PuppetError::AllowlistFull
+    );
+    config.allowed_pools.push(amm);
+    Ok(())
+}
+
+pub fn disallow_pool(ctx: Context<UpdateConfig>, amm: Pubkey) -> Result<()> {
+    let config = &mut ctx.accounts.config;
+    let index = config
+        .allowed_pools
+        .iter()
+        .position(|pool| *pool == amm)
+        .ok_or_else(|| error!(PuppetError::PoolNotAllowed))?;
+    config.allowed_pools.swap_remove(index);
+    Ok(())
+}
@@ -0,0 +1,190 @@
+use amm_anchor::Deposit;
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Mint, Token, TokenAccount};
+
+use crate::constants::*;
+use crate::errors::PuppetError;
+use crate::events::DepositEvent;
+use crate::state::Config;
+use crate::utils::{decrease, increase};
+
+/// Pool side of a Raydium deposit, shared by the user and the vault flavours
+#[derive(Accounts, Clone)]
+pub struct DepositPool<'info> {
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = !config.paused @ PuppetError::Paused,
+        constraint = config.is_pool_allowed(amm.key) @ PuppetError::PoolNotAllowed,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    /// CHECK: Safe. Only the known Raydium AMM v4 deployments are accepted
+    #[account(constraint = is_amm_program(amm_program.key) @ PuppetError::InvalidAmmProgram)]
+    pub amm_program: UncheckedAccount<'info>,
+    /// CHECK: Safe. Pool state, derived from the market by the AMM program
+    #[account(
+        mut,
+        owner = amm_program.key(),
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), AMM_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+    )]
+    pub amm: UncheckedAccount<'info>,
+    /// CHECK: Safe. AMM authority, signs for the pool vaults and the LP mint
+    #[account(seeds = [AMM_AUTHORITY_SEED], bump, seeds::program = amm_program.key())]
+    pub amm_authority: UncheckedAccount<'info>,
+    /// CHECK: Safe. Pool open orders on the market
+    #[account(
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), OPEN_ORDER_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+    )]
+    pub amm_open_orders: UncheckedAccount<'info>,
+    /// CHECK: Safe. Pool target orders
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), TARGET_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+    )]
+    pub amm_target_orders: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), LP_MINT_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+        mint::authority = amm_authority,
+    )]
+    pub amm_lp_mint: Box<Account<'info, Mint>>,
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), COIN_VAULT_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+        token::authority = amm_authority,
+    )]
+    pub amm_coin_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), PC_VAULT_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+        token::authority = amm_authority,
+    )]
+    pub amm_pc_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Safe. OpenBook market the pool is bound to, checked through the pool seeds
+    pub market: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market event queue, checked by the AMM program against the market
+    pub market_event_queue: UncheckedAccount<'info>,
+}
+
+impl<'info> DepositPool<'info> {
+    pub fn reload_vaults(&mut self) -> Result<()> {
+        self.amm_coin_vault.reload()?;
+        self.amm_pc_vault.reload()
+    }
+
+    pub fn cpi_accounts(
+        &self,
+        user_token_coin: AccountInfo<'info>,
+        user_token_pc: AccountInfo<'info>,
+        user_token_lp: AccountInfo<'info>,
+        user_owner: AccountInfo<'info>,
+        token_program: AccountInfo<'info>,
+    ) -> Deposit<'info> {
+        Deposit {
+            amm: self.amm.to_account_info(),
+            amm_authority: self.amm_authority.to_account_info(),
+            amm_open_orders: self.amm_open_orders.to_account_info(),
+            amm_target_orders: self.amm_target_orders.to_account_info(),
+            amm_lp_mint: self.amm_lp_mint.to_account_info(),
+            amm_coin_vault: self.amm_coin_vault.to_account_info(),
+            amm_pc_vault: self.amm_pc_vault.to_account_info(),
+            market: self.market.to_account_info(),
+            market_event_queue: self.market_event_queue.to_account_info(),
+            user_token_coin,
+            user_token_pc,
+            user_token_lp,
+            user_owner,
+            token_program,
+        }
+    }
+}
+
+#[derive(Accounts, Clone)]
+pub struct PuppetDeposit<'info> {
+    pub pool: DepositPool<'info>,
+    #[account(
+        mut,
+        token::mint = pool.amm_coin_vault.mint,
+        token::authority = user_owner,
+    )]
+    pub user_token_coin: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = pool.amm_pc_vault.mint,
+        token::authority = user_owner,
+    )]
+    pub user_token_pc: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        token::mint = pool.amm_lp_mint,
+        token::authority = user_owner,
+    )]
+    pub user_token_lp: Box<Account<'info, TokenAccount>>,
+    pub user_owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut PuppetDeposit<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, Deposit<'info>>
+{
+    fn from(accounts: &mut PuppetDeposit<'info>) -> CpiContext<'a, 'b, 'c, 'info, Deposit<'info>> {
+        let cpi_accounts = accounts.pool.cpi_accounts(
+            accounts.user_token_coin.to_account_info(),
+            accounts.user_token_pc.to_account_info(),
+            accounts.user_token_lp.to_account_info(),
+            accounts.user_owner.to_account_info(),
+            accounts.token_program.to_account_info(),
+        );
+        let cpi_program = accounts.pool.amm_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn deposit(
+    ctx: Context<PuppetDeposit>,
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+) -> Result<()> {
+    require!(
+        max_coin_amount > 0 && max_pc_amount > 0,
+        PuppetError::AmountZero
+    );
+    let accounts = ctx.accounts;
+    let pre_coin = accounts.user_token_coin.amount;
+    let pre_pc = accounts.user_token_pc.amount;
+    let pre_lp = accounts.user_token_lp.amount;
+    amm_anchor::deposit(
+        (&mut *accounts).into(),
+        max_coin_amount,
+        max_pc_amount,
+        base_side,
+    )?;
+
+    accounts.user_token_coin.reload()?;
+    accounts.user_token_pc.reload()?;
+    accounts.user_token_lp.reload()?;
+    accounts.pool.reload_vaults()?;
+    emit!(DepositEvent {
+        amm: accounts.pool.amm.key(),
+        user: accounts.user_owner.key(),
+        lp_minted: increase(pre_lp, accounts.user_token_lp.amount)?,
+        coin_amount: decrease(pre_coin, accounts.user_token_coin.amount)?,
+        pc_amount: decrease(pre_pc, accounts.user_token_pc.amount)?,
+        coin_vault_balance: accounts.pool.amm_coin_vault.amount,
+        pc_vault_balance: accounts.pool.amm_pc_vault.amount,
+    });
+    Ok(())
+}
@@ -0,0 +1,13 @@
+pub mod add_remove_liquidity;
+pub mod admin;
+pub mod deposit;
+pub mod swap;
+pub mod vault;
+pub mod withdraw;
+
+pub use add_remove_liquidity::*;
+pub use admin::*;
+pub use deposit::*;
+pub use swap::*;
+pub use vault::*;
+pub use withdraw::*;
@@ -0,0 +1,217 @@
+use amm_anchor::{SwapBaseIn, SwapBaseOut};
+use anchor_lang::prelude::*;
+use anchor_spl::token::{Token, TokenAccount};
+
+use crate::constants::*;
+use crate::errors::PuppetError;
+use crate::events::SwapEvent;
+use crate::state::Config;
+use crate::utils::{decrease, increase};
+
+#[derive(Accounts, Clone)]
+pub struct PuppetSwap<'info> {
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.bump,
+        constraint = !config.paused @ PuppetError::Paused,
+        constraint = config.is_pool_allowed(amm.key) @ PuppetError::PoolNotAllowed,
+    )]
+    pub config: Box<Account<'info, Config>>,
+    /// CHECK: Safe. Only the known Raydium AMM v4 deployments are accepted
+    #[account(constraint = is_amm_program(amm_program.key) @ PuppetError::InvalidAmmProgram)]
+    pub amm_program: UncheckedAccount<'info>,
+    /// CHECK: Safe. Pool state, derived from the market by the AMM program
+    #[account(
+        mut,
+        owner = amm_program.key(),
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), AMM_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+    )]
+    pub amm: UncheckedAccount<'info>,
+    /// CHECK: Safe. AMM authority, signs for the pool vaults
+    #[account(seeds = [AMM_AUTHORITY_SEED], bump, seeds::program = amm_program.key())]
+    pub amm_authority: UncheckedAccount<'info>,
+    /// CHECK: Safe. Pool open orders on the market
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), OPEN_ORDER_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+    )]
+    pub amm_open_orders: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), COIN_VAULT_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+        token::authority = amm_authority,
+    )]
+    pub amm_coin_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        seeds = [amm_program.key().as_ref(), market.key().as_ref(), PC_VAULT_ASSOCIATED_SEED],
+        bump,
+        seeds::program = amm_program.key(),
+        token::authority = amm_authority,
+    )]
+    pub amm_pc_vault: Box<Account<'info, TokenAccount>>,
+    /// CHECK: Safe. Only the known OpenBook deployments are accepted
+    #[account(constraint = is_openbook_program(market_program.key) @ PuppetError::InvalidMarketProgram)]
+    pub market_program: UncheckedAccount<'info>,
+    /// CHECK: Safe. OpenBook market the pool is bound to, checked through the pool seeds
+    #[account(mut, owner = market_program.key() @ PuppetError::InvalidMarket)]
+    pub market: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market bids, checked by the AMM program against the market
+    #[account(mut)]
+    pub market_bids: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market asks, checked by the AMM program against the market
+    #[account(mut)]
+    pub market_asks: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market event queue, checked by the AMM program against the market
+    #[account(mut)]
+    pub market_event_queue: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market coin vault, checked by the AMM program against the market
+    #[account(mut)]
+    pub market_coin_vault: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market pc vault, checked by the AMM program against the market
+    #[account(mut)]
+    pub market_pc_vault: UncheckedAccount<'info>,
+    /// CHECK: Safe. Market vault signer, checked by the AMM program against the market
+    pub market_vault_signer: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        token::authority = user_source_owner,
+        constraint = user_token_source.mint == amm_coin_vault.mint
+            || user_token_source.mint == amm_pc_vault.mint @ PuppetError::InvalidSwapMint,
+    )]
+    pub user_token_source: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = user_token_destination.mint == amm_coin_vault.mint
+            || user_token_destination.mint == amm_pc_vault.mint @ PuppetError::InvalidSwapMint,
+        constraint = user_token_destination.mint != user_token_source.mint @ PuppetError::InvalidSwapMint,
+    )]
+    pub user_token_destination: Box<Account<'info, TokenAccount>>,
+    pub user_source_owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut PuppetSwap<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, SwapBaseIn<'info>>
+{
+    fn from(accounts: &mut PuppetSwap<'info>) -> CpiContext<'a, 'b, 'c, 'info, SwapBaseIn<'info>> {
+        let cpi_accounts = SwapBaseIn {
+            amm: accounts.amm.to_account_info(),
+            amm_authority: accounts.amm_authority.to_account_info(),
+            amm_open_orders: accounts.amm_open_orders.to_account_info(),
+            amm_coin_vault: accounts.amm_coin_vault.to_account_info(),
+            amm_pc_vault: accounts.amm_pc_vault.to_account_info(),
+            market_program: accounts.market_program.to_account_info(),
+            market: accounts.market.to_account_info(),
+            market_bids: accounts.market_bids.to_account_info(),
+            market_asks: accounts.market_asks.to_account_info(),
+            market_event_queue: accounts.market_event_queue.to_account_info(),
+            market_coin_vault: accounts.market_coin_vault.to_account_info(),
+            market_pc_vault: accounts.market_pc_vault.to_account_info(),
+            market_vault_signer: accounts.market_vault_signer.to_account_info(),
+            user_token_source: accounts.user_token_source.to_account_info(),
+            user_token_destination: accounts.user_token_destination.to_account_info(),
+            user_source_owner: accounts.user_source_owner.to_account_info(),
+            token_program: accounts.token_program.to_account_info(),
+        };
+        let cpi_program = accounts.amm_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut PuppetSwap<'info>>
+    for CpiContext<'a, 'b, 'c, 'info, SwapBaseOut<'info>>
+{
+    fn from(accounts: &mut PuppetSwap<'info>) -> CpiContext<'a, 'b, 'c, 'info, SwapBaseOut<'info>> {
+        let cpi_accounts = SwapBaseOut {
+            amm: accounts.amm.to_account_info(),
+            amm_authority: accounts.amm_authority.to_account_info(),
+            amm_open_orders: accounts.amm_open_orders.to_account_info(),
+            amm_coin_vault: accounts.amm_coin_vault.to_account_info(),
+            amm_pc_vault: accounts.amm_pc_vault.to_account_info(),
+            market_program: accounts.market_program.to_account_info(),
+            market: accounts.market.to_account_info(),
+            market_bids: accounts.market_bids.to_account_info(),
+            market_asks: accounts.market_asks.to_account_info(),
+            market_event_queue: accounts.market_event_queue.to_account_info(),
+            market_coin_vault: accounts.market_coin_vault.to_account_info(),
+            market_pc_vault: accounts.market_pc_vault.to_account_info(),
+            market_vault_signer: accounts.market_vault_signer.to_account_info(),
+            user_token_source: accounts.user_token_source.to_account_info(),
+            user_token_destination: accounts.user_token_destination.to_account_info(),
+            user_source_owner: accounts.user_source_owner.to_account_info(),
+            token_program: accounts.token_program.to_account_info(),
+        };
+        let cpi_program = accounts.amm_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+pub fn swap_base_in(
+    ctx: Context<PuppetSwap>,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Result<()> {
+    require!(amount_in > 0, PuppetError::AmountZero);
+    let accounts = ctx.accounts;
+    let pre_source = accounts.user_token_source.amount;
+    let pre_destination = accounts.user_token_destination.amount;
+    // the bound is enforced below on what landed, so a slippage revert is always SlippageExceeded
+    // instead of whatever the AMM reports
+    amm_anchor::swap_base_in((&mut *accounts).into(), amount_in, 0)?;
+
+    // don't trust the CPI alone, re-read what actually landed
+    accounts.user_token_source.reload()?;
+    accounts.user_token_destination.reload()?;
+    let spent = decrease(pre_source, accounts.user_token_source.amount)?;
+    let received = increase(pre_destination, accounts.user_token_destination.amount)?;
+    msg!(
+        "swap_base_in: amount_in={} received={}",
+        amount_in,
+        received
+    );
+    require!(
+        received >= minimum_amount_out,
+        PuppetError::SlippageExceeded
+    );
+    emit_swap(accounts, spent, received)
+}
+
+pub fn swap_base_out(ctx: Context<PuppetSwap>, max_amount_in: u64, amount_out: u64) -> Result<()> {
+    require!(amount_out > 0, PuppetError::AmountZero);
+    let accounts = ctx.accounts;
+    let pre_source = accounts.user_token_source.amount;
+    let pre_destination = accounts.user_token_destination.amount;
+    // same as swap_base_in, the puppet checks the spent amount itself
+    amm_anchor::swap_base_out((&mut *accounts).into(), u64::MAX, amount_out)?;
+
+    accounts.user_token_source.reload()?;
+    accounts.user_token_destination.reload()?;
+    let spent = decrease(pre_source, accounts.user_token_source.amount)?;
+    let received = increase(pre_destination, accounts.user_token_destination.amount)?;
+    msg!("swap_base_out: spent={} received={}", spent, received);
+    require!(received >= amount_out, PuppetError::SlippageExceeded);
+    require!(spent <= max_amount_in, PuppetError::SlippageExceeded);
+    emit_swap(accounts, spent, received)
+}
+
+fn emit_swap(accounts: &mut PuppetSwap, amount_in: u64, amount_out: u64) -> Result<()> {
+    accounts.amm_coin_vault.reload()?;
+    accounts.amm_pc_vault.reload()?;
+    emit!(SwapEvent {
+        amm: accounts.amm.key(),
+        user: accounts.user_source_owner.key(),
+        input_mint: accounts.user_token_source.mint,
+        amount_in,
+        amount_out,
+        coin_vault_balance: accounts.amm_coin_vault.amount,
+        pc_vault_balance: accounts.amm_pc_vault.amount,
+    });
+    Ok(())
+}
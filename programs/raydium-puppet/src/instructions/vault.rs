@@ -0,0 +1,423 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+
+use crate::constants::*;
+use crate::errors::PuppetError;
+use crate::events::{DepositEvent, WithdrawEvent};
+use crate::instructions::{DepositPool, WithdrawPool};
+use crate::state::{Vault, VaultPosition};
+use crate::utils::{decrease, increase};
+
+#[derive(Accounts)]
+pub struct OpenVault<'info> {
+    pub pool: DepositPool<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + Vault::INIT_SPACE,
+        seeds = [VAULT_SEED, pool.amm.key().as_ref()],
+        bump,
+    )]
+    pub vault: Account<'info, Vault>,
+    #[account(address = pool.amm_coin_vault.mint)]
+    pub coin_mint: Box<Account<'info, Mint>>,
+    #[account(address = pool.amm_pc_vault.mint)]
+    pub pc_mint: Box<Account<'info, Mint>>,
+    #[account(address = pool.amm_lp_mint.key())]
+    pub lp_mint: Box<Account<'info, Mint>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = coin_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_coin: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = pc_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_pc: Box<Account<'info, TokenAccount>>,
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = lp_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_lp: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct OpenVaultPosition<'info> {
+    #[account(seeds = [VAULT_SEED, vault.amm.as_ref()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + VaultPosition::INIT_SPACE,
+        seeds = [VAULT_POSITION_SEED, vault.key().as_ref(), owner.key().as_ref()],
+        bump,
+    )]
+    pub position: Account<'info, VaultPosition>,
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+/// Moves idle tokens between the owner and the vault, used by both `vault_fund` and `vault_reclaim`
+#[derive(Accounts)]
+pub struct VaultTransfer<'info> {
+    #[account(seeds = [VAULT_SEED, vault.amm.as_ref()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [VAULT_POSITION_SEED, vault.key().as_ref(), owner.key().as_ref()],
+        bump = position.bump,
+        has_one = owner,
+    )]
+    pub position: Account<'info, VaultPosition>,
+    #[account(
+        mut,
+        associated_token::mint = vault.coin_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_coin: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = vault.pc_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_pc: Box<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = vault.coin_mint, token::authority = owner)]
+    pub owner_token_coin: Box<Account<'info, TokenAccount>>,
+    #[account(mut, token::mint = vault.pc_mint, token::authority = owner)]
+    pub owner_token_pc: Box<Account<'info, TokenAccount>>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct VaultDeposit<'info> {
+    pub pool: DepositPool<'info>,
+    #[account(mut, seeds = [VAULT_SEED, pool.amm.key().as_ref()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [VAULT_POSITION_SEED, vault.key().as_ref(), owner.key().as_ref()],
+        bump = position.bump,
+        has_one = owner,
+    )]
+    pub position: Account<'info, VaultPosition>,
+    #[account(
+        mut,
+        associated_token::mint = vault.coin_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_coin: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = vault.pc_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_pc: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = vault.lp_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_lp: Box<Account<'info, TokenAccount>>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct VaultWithdraw<'info> {
+    pub pool: WithdrawPool<'info>,
+    #[account(mut, seeds = [VAULT_SEED, pool.amm.key().as_ref()], bump = vault.bump)]
+    pub vault: Account<'info, Vault>,
+    #[account(
+        mut,
+        seeds = [VAULT_POSITION_SEED, vault.key().as_ref(), owner.key().as_ref()],
+        bump = position.bump,
+        has_one = owner,
+    )]
+    pub position: Account<'info, VaultPosition>,
+    #[account(
+        mut,
+        associated_token::mint = vault.coin_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_coin: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = vault.pc_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_pc: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        associated_token::mint = vault.lp_mint,
+        associated_token::authority = vault,
+    )]
+    pub vault_token_lp: Box<Account<'info, TokenAccount>>,
+    pub owner: Signer<'info>,
+    pub token_program: Program<'info, Token>,
+}
+
+pub fn open_vault(ctx: Context<OpenVault>) -> Result<()> {
+    let vault = &mut ctx.accounts.vault;
+    vault.amm = ctx.accounts.pool.amm.key();
+    vault.coin_mint = ctx.accounts.coin_mint.key();
+    vault.pc_mint = ctx.accounts.pc_mint.key();
+    vault.lp_mint = ctx.accounts.lp_mint.key();
+    vault.bump = ctx.bumps.vault;
+    vault.total_shares = 0;
+    Ok(())
+}
+
+pub fn open_vault_position(ctx: Context<OpenVaultPosition>) -> Result<()> {
+    let position = &mut ctx.accounts.position;
+    position.vault = ctx.accounts.vault.key();
+    position.owner = ctx.accounts.owner.key();
+    position.bump = ctx.bumps.position;
+    position.coin_balance = 0;
+    position.pc_balance = 0;
+    position.shares = 0;
+    Ok(())
+}
+
+pub fn vault_fund(ctx: Context<VaultTransfer>, coin_amount: u64, pc_amount: u64) -> Result<()> {
+    require!(coin_amount > 0 || pc_amount > 0, PuppetError::AmountZero);
+    let accounts = ctx.accounts;
+    let token_program = accounts.token_program.to_account_info();
+    let authority = accounts.owner.to_account_info();
+    for (from, to, amount) in [
+        (
+            &accounts.owner_token_coin,
+            &accounts.vault_token_coin,
+            coin_amount,
+        ),
+        (
+            &accounts.owner_token_pc,
+            &accounts.vault_token_pc,
+            pc_amount,
+        ),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        let cpi_accounts = Transfer {
+            from: from.to_account_info(),
+            to: to.to_account_info(),
+            authority: authority.clone(),
+        };
+        token::transfer(CpiContext::new(token_program.clone(), cpi_accounts), amount)?;
+    }
+
+    let position = &mut accounts.position;
+    position.coin_balance = checked_add(position.coin_balance, coin_amount)?;
+    position.pc_balance = checked_add(position.pc_balance, pc_amount)?;
+    Ok(())
+}
+
+pub fn vault_reclaim(ctx: Context<VaultTransfer>, coin_amount: u64, pc_amount: u64) -> Result<()> {
+    require!(coin_amount > 0 || pc_amount > 0, PuppetError::AmountZero);
+    let accounts = ctx.accounts;
+    let position = &mut accounts.position;
+    position.coin_balance = position
+        .coin_balance
+        .checked_sub(coin_amount)
+        .ok_or_else(|| error!(PuppetError::InsufficientVaultBalance))?;
+    position.pc_balance = position
+        .pc_balance
+        .checked_sub(pc_amount)
+        .ok_or_else(|| error!(PuppetError::InsufficientVaultBalance))?;
+
+    let (amm, bump) = (accounts.vault.amm, accounts.vault.bump);
+    let signer_seeds: &[&[&[u8]]] = &[&[VAULT_SEED, amm.as_ref(), &[bump]]];
+    let token_program = accounts.token_program.to_account_info();
+    let authority = accounts.vault.to_account_info();
+    for (from, to, amount) in [
+        (
+            &accounts.vault_token_coin,
+            &accounts.owner_token_coin,
+            coin_amount,
+        ),
+        (
+            &accounts.vault_token_pc,
+            &accounts.owner_token_pc,
+            pc_amount,
+        ),
+    ] {
+        if amount == 0 {
+            continue;
+        }
+        let cpi_accounts = Transfer {
+            from: from.to_account_info(),
+            to: to.to_account_info(),
+            authority: authority.clone(),
+        };
+        token::transfer(
+            CpiContext::new_with_signer(token_program.clone(), cpi_accounts, signer_seeds),
+            amount,
+        )?;
+    }
+    Ok(())
+}
+
+pub fn vault_deposit(
+    ctx: Context<VaultDeposit>,
+    max_coin_amount: u64,
+    max_pc_amount: u64,
+    base_side: u64,
+) -> Result<()> {
+    require!(
+        max_coin_amount > 0 && max_pc_amount > 0,
+        PuppetError::AmountZero
+    );
+    let accounts = ctx.accounts;
+    require!(
+        max_coin_amount <= accounts.position.coin_balance
+            && max_pc_amount <= accounts.position.pc_balance,
+        PuppetError::InsufficientVaultBalance
+    );
+
+    let pre_coin = accounts.vault_token_coin.amount;
+    let pre_pc = accounts.vault_token_pc.amount;
+    let pre_lp = accounts.vault_token_lp.amount;
+
+    let (amm, bump) = (accounts.vault.amm, accounts.vault.bump);
+    let signer_seeds: &[&[&[u8]]] = &[&[VAULT_SEED, amm.as_ref(), &[bump]]];
+    let cpi_accounts = accounts.pool.cpi_accounts(
+        accounts.vault_token_coin.to_account_info(),
+        accounts.vault_token_pc.to_account_info(),
+        accounts.vault_token_lp.to_account_info(),
+        accounts.vault.to_account_info(),
+        accounts.token_program.to_account_info(),
+    );
+    amm_anchor::deposit(
+        CpiContext::new_with_signer(
+            accounts.pool.amm_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        ),
+        max_coin_amount,
+        max_pc_amount,
+        base_side,
+    )?;
+
+    accounts.vault_token_coin.reload()?;
+    accounts.vault_token_pc.reload()?;
+    accounts.vault_token_lp.reload()?;
+    let coin_in = decrease(pre_coin, accounts.vault_token_coin.amount)?;
+    let pc_in = decrease(pre_pc, accounts.vault_token_pc.amount)?;
+    let lp_minted = increase(pre_lp, accounts.vault_token_lp.amount)?;
+    accounts.pool.reload_vaults()?;
+    emit!(DepositEvent {
+        amm: accounts.pool.amm.key(),
+        user: accounts.owner.key(),
+        lp_minted,
+        coin_amount: coin_in,
+        pc_amount: pc_in,
+        coin_vault_balance: accounts.pool.amm_coin_vault.amount,
+        pc_vault_balance: accounts.pool.amm_pc_vault.amount,
+    });
+
+    // shares are minted against the LP the vault held before, so earlier positions keep their value
+    let shares = accounts
+        .vault
+        .shares_for_lp(lp_minted, pre_lp)
+        .ok_or_else(|| error!(PuppetError::MathOverflow))?;
+    require!(shares > 0, PuppetError::AmountZero);
+    let vault = &mut accounts.vault;
+    vault.total_shares = checked_add(vault.total_shares, shares)?;
+    let position = &mut accounts.position;
+    position.coin_balance = position
+        .coin_balance
+        .checked_sub(coin_in)
+        .ok_or_else(|| error!(PuppetError::InsufficientVaultBalance))?;
+    position.pc_balance = position
+        .pc_balance
+        .checked_sub(pc_in)
+        .ok_or_else(|| error!(PuppetError::InsufficientVaultBalance))?;
+    position.shares = checked_add(position.shares, shares)?;
+    Ok(())
+}
+
+pub fn vault_withdraw(ctx: Context<VaultWithdraw>, shares: u64) -> Result<()> {
+    require!(shares > 0, PuppetError::AmountZero);
+    let accounts = ctx.accounts;
+    require!(
+        shares <= accounts.position.shares,
+        PuppetError::InsufficientShares
+    );
+    let lp_amount = accounts
+        .vault
+        .lp_for_shares(shares, accounts.vault_token_lp.amount)
+        .ok_or_else(|| error!(PuppetError::MathOverflow))?;
+    require!(lp_amount > 0, PuppetError::AmountZero);
+
+    let pre_coin = accounts.vault_token_coin.amount;
+    let pre_pc = accounts.vault_token_pc.amount;
+    let pre_lp = accounts.vault_token_lp.amount;
+
+    let (amm, bump) = (accounts.vault.amm, accounts.vault.bump);
+    let signer_seeds: &[&[&[u8]]] = &[&[VAULT_SEED, amm.as_ref(), &[bump]]];
+    let cpi_accounts = accounts.pool.cpi_accounts(
+        accounts.vault_token_lp.to_account_info(),
+        accounts.vault_token_coin.to_account_info(),
+        accounts.vault_token_pc.to_account_info(),
+        accounts.vault.to_account_info(),
+        accounts.token_program.to_account_info(),
+    );
+    amm_anchor::withdraw(
+        CpiContext::new_with_signer(
+            accounts.pool.amm_program.to_account_info(),
+            cpi_accounts,
+            signer_seeds,
+        ),
+        lp_amount,
+    )?;
+
+    accounts.vault_token_coin.reload()?;
+    accounts.vault_token_pc.reload()?;
+    accounts.vault_token_lp.reload()?;
+    let coin_out = increase(pre_coin, accounts.vault_token_coin.amount)?;
+    let pc_out = increase(pre_pc, accounts.vault_token_pc.amount)?;
+    let lp_burned = decrease(pre_lp, accounts.vault_token_lp.amount)?;
+    accounts.pool.reload_vaults()?;
+    emit!(WithdrawEvent {
+        amm: accounts.pool.amm.key(),
+        user: accounts.owner.key(),
+        lp_burned,
+        coin_amount: coin_out,
+        pc_amount: pc_out,
+        coin_vault_balance: accounts.pool.amm_coin_vault.amount,
+        pc_vault_balance: accounts.pool.amm_pc_vault.amount,
+    });
+
+    // the withdrawn tokens stay in the vault as the owner's idle balance
+    let vault = &mut accounts.vault;
+    vault.total_shares = vault
+        .total_shares
+        .checked_sub(shares)
+        .ok_or_else(|| error!(PuppetError::InsufficientShares))?;
+    let position = &mut accounts.position;
+    position.coin_balance = checked_add(position.coin_balance, coin_out)?;
+    position.pc_balance = checked_add(position.pc_balance, pc_out)?;
+    position.shares -= shares;
+    Ok(())
+}
+
+fn checked_add(balance: u64, amount: u64) -> Result<u64> {
+    balance
+        .checked_add(amount)
+        .ok_or_else(|| error!(PuppetError::MathOverflow))
+}
@@ -1,15 +1,133 @@
 use anchor_lang::prelude::*;
 
+pub mod constants;
+pub mod errors;
+pub mod events;
+pub mod instructions;
+pub mod state;
+mod utils;
+
+use instructions::*;
+
 declare_id!("3kZmRLYfSmV9sYTH7upYwtLGmKkXRRMvoXwQLRnPchm2");
 
 #[program]
 pub mod raydium_puppet {
     use super::*;
 
-    pub fn initialize(_ctx: Context<Initialize>) -> Result<()> {
-        todo!()
+    /// Creates the admin config, the program upgrade authority signs and becomes its authority
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        instructions::initialize_config(ctx)
+    }
+
+    pub fn transfer_authority(ctx: Context<UpdateConfig>, new_authority: Pubkey) -> Result<()> {
+        instructions::transfer_authority(ctx, new_authority)
+    }
+
+    /// Halts (or resumes) every liquidity instruction
+    pub fn set_paused(ctx: Context<UpdateConfig>, paused: bool) -> Result<()> {
+        instructions::set_paused(ctx, paused)
+    }
+
+    pub fn allow_pool(ctx: Context<UpdateConfig>, amm: Pubkey) -> Result<()> {
+        instructions::allow_pool(ctx, amm)
+    }
+
+    pub fn disallow_pool(ctx: Context<UpdateConfig>, amm: Pubkey) -> Result<()> {
+        instructions::disallow_pool(ctx, amm)
+    }
+
+    /// Adds liquidity to a Raydium AMM v4 pool, `base_side` 0 fixes the coin amount, 1 the pc amount
+    pub fn deposit(
+        ctx: Context<PuppetDeposit>,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        base_side: u64,
+    ) -> Result<()> {
+        instructions::deposit(ctx, max_coin_amount, max_pc_amount, base_side)
+    }
+
+    /// Burns `amount` LP tokens and withdraws the underlying coin and pc
+    pub fn withdraw(ctx: Context<PuppetWithdraw>, amount: u64) -> Result<()> {
+        instructions::withdraw(ctx, amount)
+    }
+
+    /// Deposits into one pool then withdraws from another (or the same) in a single instruction,
+    /// reverting if the LP or token deltas fall outside of `bounds`
+    pub fn add_remove_liquidity(
+        ctx: Context<PuppetAddRemoveLiquidity>,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        base_side: u64,
+        withdraw_lp_amount: u64,
+        bounds: AddRemoveBounds,
+    ) -> Result<()> {
+        instructions::add_remove_liquidity(
+            ctx,
+            max_coin_amount,
+            max_pc_amount,
+            base_side,
+            withdraw_lp_amount,
+            bounds,
+        )
     }
-}
 
-#[derive(Accounts)]
-pub struct Initialize {}
+    /// Swaps exactly `amount_in`, failing if less than `minimum_amount_out` lands in the destination
+    pub fn swap_base_in(
+        ctx: Context<PuppetSwap>,
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> Result<()> {
+        instructions::swap_base_in(ctx, amount_in, minimum_amount_out)
+    }
+
+    /// Swaps for exactly `amount_out`, spending at most `max_amount_in`
+    pub fn swap_base_out(
+        ctx: Context<PuppetSwap>,
+        max_amount_in: u64,
+        amount_out: u64,
+    ) -> Result<()> {
+        instructions::swap_base_out(ctx, max_amount_in, amount_out)
+    }
+
+    /// Opens the pool level vault, along with its coin, pc and LP token accounts
+    pub fn open_vault(ctx: Context<OpenVault>) -> Result<()> {
+        instructions::open_vault(ctx)
+    }
+
+    /// Opens the caller's position in a vault
+    pub fn open_vault_position(ctx: Context<OpenVaultPosition>) -> Result<()> {
+        instructions::open_vault_position(ctx)
+    }
+
+    /// Moves idle tokens from the owner into the vault, credited to their position
+    pub fn vault_fund(ctx: Context<VaultTransfer>, coin_amount: u64, pc_amount: u64) -> Result<()> {
+        instructions::vault_fund(ctx, coin_amount, pc_amount)
+    }
+
+    /// Moves the owner's idle tokens from the vault back to them
+    pub fn vault_reclaim(
+        ctx: Context<VaultTransfer>,
+        coin_amount: u64,
+        pc_amount: u64,
+    ) -> Result<()> {
+        instructions::vault_reclaim(ctx, coin_amount, pc_amount)
+    }
+
+    /// Adds the owner's idle coin and pc to the pool, the vault PDA signs the CPI and the owner
+    /// gets shares pro rata to the LP minted
+    pub fn vault_deposit(
+        ctx: Context<VaultDeposit>,
+        max_coin_amount: u64,
+        max_pc_amount: u64,
+        base_side: u64,
+    ) -> Result<()> {
+        instructions::vault_deposit(ctx, max_coin_amount, max_pc_amount, base_side)
+    }
+
+    /// Burns `shares` for their part of the vault LP, the underlying coin and pc become the
+    /// owner's idle balance
+    pub fn vault_withdraw(ctx: Context<VaultWithdraw>, shares: u64) -> Result<()> {
+        instructions::vault_withdraw(ctx, shares)
+    }
+}
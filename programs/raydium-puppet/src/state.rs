@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::MAX_ALLOWED_POOLS;
+
+/// Program wide admin settings, every liquidity instruction checks it before touching a pool
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Config {
+    pub authority: Pubkey,
+    pub paused: bool,
+    pub bump: u8,
+    /// AMM ids the puppet is allowed to operate on
+    #[max_len(MAX_ALLOWED_POOLS)]
+    pub allowed_pools: Vec<Pubkey>,
+}
+
+impl Config {
+    pub fn is_pool_allowed(&self, amm: &Pubkey) -> bool {
+        self.allowed_pools.contains(amm)
+    }
+}
+
+/// Pool level vault shared by every depositor. The PDA owns the vault token accounts and signs
+/// the Raydium CPIs, the LP it holds backs `total_shares`
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct Vault {
+    pub amm: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub lp_mint: Pubkey,
+    pub bump: u8,
+    /// Shares held by all the positions
+    pub total_shares: u64,
+}
+
+/// A depositor's claim on a vault: shares of its LP, plus the idle tokens they funded
+#[account]
+#[derive(InitSpace, Debug)]
+pub struct VaultPosition {
+    pub vault: Pubkey,
+    pub owner: Pubkey,
+    pub bump: u8,
+    /// Idle coin credited to the owner
+    pub coin_balance: u64,
+    /// Idle pc credited to the owner
+    pub pc_balance: u64,
+    pub shares: u64,
+}
+
+impl Vault {
+    /// Shares minted for `lp_minted` when the vault held `vault_lp` before, the first deposit
+    /// gets one share per LP token
+    pub fn shares_for_lp(&self, lp_minted: u64, vault_lp: u64) -> Option<u64> {
+        if self.total_shares == 0 {
+            return Some(lp_minted);
+        }
+        mul_div(lp_minted, self.total_shares, vault_lp)
+    }
+
+    /// LP backing `shares` out of the `vault_lp` held, rounded down
+    pub fn lp_for_shares(&self, shares: u64, vault_lp: u64) -> Option<u64> {
+        mul_div(shares, vault_lp, self.total_shares)
+    }
+}
+
+fn mul_div(amount: u64, numerator: u64, denominator: u64) -> Option<u64> {
+    (amount as u128)
+        .checked_mul(numerator as u128)?
+        .checked_div(denominator as u128)?
+        .try_into()
+        .ok()
+}
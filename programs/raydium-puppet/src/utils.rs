@@ -0,0 +1,17 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::PuppetError;
+
+/// How much a token balance grew across a CPI
+pub(crate) fn increase(before: u64, after: u64) -> Result<u64> {
+    after
+        .checked_sub(before)
+        .ok_or_else(|| error!(PuppetError::UnexpectedBalanceChange))
+}
+
+/// How much a token balance shrank across a CPI
+pub(crate) fn decrease(before: u64, after: u64) -> Result<u64> {
+    before
+        .checked_sub(after)
+        .ok_or_else(|| error!(PuppetError::UnexpectedBalanceChange))
+}